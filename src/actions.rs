@@ -1,9 +1,8 @@
-use leafwing_input_manager::Actionlike;
-use bevy::reflect::Reflect;
+use bevy::prelude::*;
+use leafwing_input_manager::prelude::*;
+use serde::{Deserialize, Serialize};
 
-#[derive(
-    Actionlike, PartialEq, Eq, Clone, Copy, Hash, Debug, Reflect
-)]
+#[derive(Actionlike, PartialEq, Eq, Clone, Copy, Hash, Debug, Reflect, Serialize, Deserialize)]
 pub enum PlatformerAction {
     Right,
     Left,
@@ -21,3 +20,47 @@ pub enum PlatformerAction {
     Menus,
 }
 
+/// The controls the game ships with, W/A/S/D and Space on the keyboard and
+/// the first gamepad.
+pub fn default_input_map() -> InputMap<PlatformerAction> {
+    use PlatformerAction::*;
+
+    let mut input_map = InputMap::default();
+
+    // basic movement
+    input_map.insert(KeyCode::W, Up);
+    input_map.insert(GamepadButtonType::DPadUp, Up);
+
+    input_map.insert(KeyCode::S, Down);
+    input_map.insert(GamepadButtonType::DPadDown, Down);
+
+    input_map.insert(KeyCode::A, Left);
+    input_map.insert(GamepadButtonType::DPadLeft, Left);
+
+    input_map.insert(
+        SingleAxis::symmetric(GamepadAxisType::LeftStickX, 0.1),
+        Horizontal,
+    );
+
+    input_map.insert(KeyCode::D, Right);
+    input_map.insert(GamepadButtonType::DPadRight, Right);
+
+    // Jump
+    input_map.insert(KeyCode::Space, PlatformerAction::Jump);
+    input_map.insert(GamepadButtonType::South, PlatformerAction::Jump);
+
+    input_map.insert(KeyCode::E, PlatformerAction::Dash);
+    input_map.insert(GamepadButtonType::RightTrigger2, PlatformerAction::Dash);
+
+    input_map.insert(KeyCode::Q, PlatformerAction::Heal);
+    input_map.insert(GamepadButtonType::West, PlatformerAction::Heal);
+
+    input_map.insert(KeyCode::Return, PlatformerAction::Pause);
+    input_map.insert(GamepadButtonType::Start, PlatformerAction::Pause);
+
+    input_map.insert(KeyCode::I, PlatformerAction::Menus);
+    input_map.insert(GamepadButtonType::Select, PlatformerAction::Menus);
+    input_map.set_gamepad(Gamepad { id: 0 });
+
+    input_map
+}
@@ -0,0 +1,245 @@
+/*
+    Player sprite animation driven by the player state machine.
+
+    `PlayerAnimations` describes the character sheet: the size of a frame,
+    how the frames are laid out, and which run of frames plays at which rate
+    for every animation. Each `State` maps to one animation, so adding a state
+    means adding it to `Animation::of`. The clip only changes when the state
+    machine reports a transition, except in Idle, which switches between
+    standing and running with the player's speed. The sprite is flipped to
+    the way the player faces.
+*/
+
+use crate::game_state::GameState;
+use crate::player::{change_character_position, PlayerState, State};
+use crate::player_components::{MovementState, Player};
+use crate::wrap::spawn_wrap_ghosts;
+use bevy::prelude::*;
+
+use std::collections::HashMap;
+
+pub struct AnimationPlugin;
+
+impl Plugin for AnimationPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<PlayerAnimations>().add_systems(
+            Update,
+            (
+                // the wrap ghost copies the player's atlas when it spawns
+                attach_animator.before(spawn_wrap_ghosts),
+                (pick_animation, animate)
+                    .chain()
+                    .after(change_character_position)
+                    .run_if(in_state(GameState::Playing)),
+            )
+                .chain(),
+        );
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Animation {
+    Idle,
+    Run,
+    Jump,
+    Fall,
+    Dash,
+    Heal,
+    Crouch,
+    WallSlide,
+}
+
+impl Animation {
+    /// The animation shown in `state`, `running` telling standing and running apart.
+    pub fn of(state: &State, running: bool) -> Self {
+        match state {
+            State::Idle {} if running => Animation::Run,
+            State::Idle {} => Animation::Idle,
+            State::Jumping {} => Animation::Jump,
+            State::Falling {} => Animation::Fall,
+            State::Dashing {} => Animation::Dash,
+            State::Healing {} => Animation::Heal,
+            State::Crouching {} => Animation::Crouch,
+            State::WallSliding {} => Animation::WallSlide,
+        }
+    }
+}
+
+/// A run of frames on the sheet, from `first` to `last` inclusive.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Clip {
+    pub first: usize,
+    pub last: usize,
+    /// Frames per second.
+    pub fps: f32,
+    /// Starts over after the last frame, otherwise holds it.
+    pub looping: bool,
+}
+
+impl Clip {
+    fn new(first: usize, last: usize, fps: f32, looping: bool) -> Self {
+        Self {
+            first,
+            last,
+            fps,
+            looping,
+        }
+    }
+
+    fn len(&self) -> usize {
+        self.last - self.first + 1
+    }
+
+    /// The frame `steps` frames after `frame`, counted from the start of the clip.
+    fn advance(&self, frame: usize, steps: usize) -> usize {
+        if self.looping {
+            (frame + steps) % self.len()
+        } else {
+            (frame + steps).min(self.len() - 1)
+        }
+    }
+}
+
+/// How the player's sheet is laid out and which frames every animation uses.
+#[derive(Resource, Clone, Debug)]
+pub struct PlayerAnimations {
+    /// Path of the sheet, relative to the assets folder.
+    pub sheet: String,
+    /// Size of one frame in pixels.
+    pub frame_size: Vec2,
+    pub columns: usize,
+    pub rows: usize,
+    /// Horizontal speed above which Idle shows `Animation::Run`, in pixels per second.
+    pub run_threshold: f32,
+    pub clips: HashMap<Animation, Clip>,
+}
+
+impl Default for PlayerAnimations {
+    fn default() -> Self {
+        // AnimationSheet_Character.png has one animation per row of eight
+        // frames: idle, idle blinking, walk, run, crouch, jump, disappear,
+        // die and attack
+        Self {
+            sheet: "AnimationSheet_Character.png".to_string(),
+            frame_size: Vec2::splat(32.),
+            columns: 8,
+            rows: 9,
+            run_threshold: 10.,
+            clips: HashMap::from([
+                (Animation::Idle, Clip::new(0, 1, 2., true)),
+                (Animation::Run, Clip::new(24, 31, 12., true)),
+                (Animation::Jump, Clip::new(40, 43, 10., false)),
+                (Animation::Fall, Clip::new(44, 47, 10., false)),
+                (Animation::Dash, Clip::new(24, 31, 24., true)),
+                (Animation::Heal, Clip::new(8, 9, 4., true)),
+                (Animation::Crouch, Clip::new(32, 37, 16., false)),
+                (Animation::WallSlide, Clip::new(44, 44, 1., false)),
+            ]),
+        }
+    }
+}
+
+/// The animation playing on the player and where it is in it.
+#[derive(Component)]
+pub struct Animator {
+    pub animation: Animation,
+    /// Frame of the clip, counted from its first one.
+    pub frame: usize,
+    timer: Timer,
+}
+
+impl Animator {
+    fn play(&mut self, animation: Animation, clip: &Clip) {
+        self.animation = animation;
+        self.frame = 0;
+        self.timer = Timer::from_seconds(1. / clip.fps, TimerMode::Repeating);
+    }
+}
+
+// The sheet is cut into a grid of its own: the atlas LDtk builds follows the
+// tileset's grid size, not the size of the player's frames.
+fn attach_animator(
+    mut commands: Commands,
+    animations: Res<PlayerAnimations>,
+    asset_server: Res<AssetServer>,
+    mut texture_atlases: ResMut<Assets<TextureAtlas>>,
+    mut players: Query<(Entity, &mut Handle<TextureAtlas>), Added<Player>>,
+) {
+    for (player, mut texture_atlas) in &mut players {
+        *texture_atlas = texture_atlases.add(TextureAtlas::from_grid(
+            asset_server.load(&animations.sheet),
+            animations.frame_size,
+            animations.columns,
+            animations.rows,
+            None,
+            None,
+        ));
+
+        // picked for real on the player state machine's first report
+        commands.entity(player).insert(Animator {
+            animation: Animation::Idle,
+            frame: 0,
+            timer: Timer::default(),
+        });
+    }
+}
+
+fn pick_animation(
+    animations: Res<PlayerAnimations>,
+    mut players: Query<
+        (
+            &mut PlayerState,
+            &MovementState,
+            &mut Animator,
+            &mut TextureAtlasSprite,
+        ),
+        With<Player>,
+    >,
+) {
+    for (mut state, movement, mut animator, mut sprite) in &mut players {
+        sprite.flip_x = movement.facing < 0.;
+
+        let transitioned = state.take_transition();
+        if !transitioned && !matches!(state.state(), State::Idle {}) {
+            continue;
+        }
+
+        let running = movement.velocity.abs() > animations.run_threshold;
+        let animation = Animation::of(state.state(), running);
+        if transitioned || animation != animator.animation {
+            if let Some(clip) = animations.clips.get(&animation) {
+                animator.play(animation, clip);
+            }
+        }
+    }
+}
+
+fn animate(
+    animations: Res<PlayerAnimations>,
+    mut players: Query<(&mut Animator, &mut TextureAtlasSprite)>,
+    time: Res<Time>,
+) {
+    for (mut animator, mut sprite) in &mut players {
+        let Some(clip) = animations.clips.get(&animator.animation) else {
+            continue;
+        };
+
+        let steps = animator.timer.tick(time.delta()).times_finished_this_tick() as usize;
+        animator.frame = clip.advance(animator.frame, steps);
+        sprite.index = clip.first + animator.frame;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn looping_clips_wrap_and_others_hold_their_last_frame() {
+        let looping = Clip::new(24, 31, 12., true);
+        assert_eq!(looping.advance(6, 3), 1);
+
+        let once = Clip::new(40, 43, 10., false);
+        assert_eq!(once.advance(2, 5), 3);
+    }
+}
@@ -0,0 +1,278 @@
+/*
+    Camera that follows the player around the selected level.
+
+    The player can move inside a dead-zone without the camera reacting, the
+    camera leads a little in the direction the player is heading, and it eases
+    towards its target with a critically damped spring instead of snapping. The
+    view is sized to fill the window at any aspect ratio without showing
+    anything outside the level, except above it in endless mode.
+*/
+
+use crate::death::Dying;
+use crate::endless::EndlessTower;
+use crate::events::{DeathCause, PlayerDied, PlayerRespawned};
+use crate::game_state::GameState;
+use crate::player_components::Player;
+use bevy::prelude::*;
+use bevy::render::camera::ScalingMode;
+use bevy::transform::TransformSystem;
+use bevy::window::PrimaryWindow;
+use bevy_ecs_ldtk::prelude::*;
+use bevy_rapier2d::prelude::*;
+
+pub struct CameraPlugin;
+
+impl Plugin for CameraPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<CameraSettings>()
+            .add_systems(Startup, setup_camera)
+            // follow where physics just put the player, before it gets rendered
+            .add_systems(
+                PostUpdate,
+                follow_player
+                    .after(PhysicsSet::Writeback)
+                    .before(TransformSystem::TransformPropagate),
+            )
+            .add_systems(Update, fall_off_screen.run_if(in_state(GameState::Playing)));
+    }
+}
+
+#[derive(Resource, Clone, Debug)]
+pub struct CameraSettings {
+    /// Roughly how long the camera takes to catch up with its target, in seconds.
+    pub smooth_time: f32,
+    /// Half extents of the box around the camera's focus the player can move in
+    /// without the camera following, in pixels.
+    pub dead_zone: Vec2,
+    /// How far ahead of the player the camera looks when they move sideways, in pixels.
+    pub look_ahead: f32,
+    /// Doodle Jump style: the camera only ever scrolls up, and dropping below the
+    /// bottom of the screen is fatal.
+    pub never_scroll_down: bool,
+}
+
+impl Default for CameraSettings {
+    fn default() -> Self {
+        Self {
+            smooth_time: 0.2,
+            dead_zone: Vec2::new(16., 24.),
+            look_ahead: 24.,
+            never_scroll_down: false,
+        }
+    }
+}
+
+#[derive(Component, Default)]
+pub struct GameCamera {
+    /// The point the dead-zone is centered on.
+    focus: Vec2,
+    velocity: Vec2,
+    look_ahead: f32,
+    look_ahead_velocity: f32,
+    /// The level the camera was last following in, it snaps when this changes or
+    /// the player respawns.
+    level: Option<LevelIid>,
+}
+
+pub fn setup_camera(mut commands: Commands) {
+    commands.spawn((Camera2dBundle::default(), GameCamera::default()));
+}
+
+/// Unity style SmoothDamp, a critically damped spring towards `target`.
+fn smooth_damp(current: f32, target: f32, velocity: &mut f32, smooth_time: f32, dt: f32) -> f32 {
+    let omega = 2. / smooth_time.max(0.0001);
+    let x = omega * dt;
+    let decay = 1. / (1. + x + 0.48 * x * x + 0.235 * x * x * x);
+    let change = current - target;
+    let temp = (*velocity + omega * change) * dt;
+    *velocity = (*velocity - omega * temp) * decay;
+    target + (change + temp) * decay
+}
+
+// Centers the view on an axis where the level is smaller than the screen.
+fn clamp_or_center(value: f32, min: f32, max: f32) -> f32 {
+    if min > max {
+        (min + max) / 2.
+    } else {
+        value.clamp(min, max)
+    }
+}
+
+#[allow(clippy::type_complexity, clippy::too_many_arguments)]
+fn follow_player(
+    mut camera_query: Query<
+        (&mut GameCamera, &mut OrthographicProjection, &mut Transform),
+        Without<Player>,
+    >,
+    player_query: Query<
+        (
+            &Transform,
+            Option<&KinematicCharacterControllerOutput>,
+            Ref<Player>,
+        ),
+        Without<LevelIid>,
+    >,
+    level_query: Query<(&Transform, &LevelIid), (Without<GameCamera>, Without<Player>)>,
+    window_query: Query<&Window, With<PrimaryWindow>>,
+    ldtk_projects: Query<&Handle<LdtkProject>>,
+    ldtk_project_assets: Res<Assets<LdtkProject>>,
+    level_selection: Res<LevelSelection>,
+    settings: Res<CameraSettings>,
+    endless_tower: Option<Res<EndlessTower>>,
+    mut respawn_events: EventReader<PlayerRespawned>,
+    time: Res<Time>,
+) {
+    let respawned = respawn_events.read().count() > 0;
+
+    let Ok((mut camera, mut projection, mut camera_transform)) = camera_query.get_single_mut()
+    else {
+        return;
+    };
+    let Ok((player_transform, output, player)) = player_query.get_single() else {
+        return;
+    };
+    let Ok(window) = window_query.get_single() else {
+        return;
+    };
+    if window.width() <= 0. || window.height() <= 0. {
+        return;
+    }
+    let Some(ldtk_project) = ldtk_projects
+        .get_single()
+        .ok()
+        .and_then(|handle| ldtk_project_assets.get(handle))
+    else {
+        return;
+    };
+
+    let Some((level_transform, level_iid, level)) =
+        level_query.iter().find_map(|(level_transform, level_iid)| {
+            let level = ldtk_project.get_raw_level_by_iid(&level_iid.to_string())?;
+            level_selection
+                .is_match(&LevelIndices::default(), level)
+                .then_some((level_transform, level_iid, level))
+        })
+    else {
+        return;
+    };
+
+    // fill the window, fitting whichever side of the level is shorter relative to it
+    let window_ratio = window.width() / window.height();
+    let level_size = Vec2::new(level.px_wid as f32, level.px_hei as f32);
+    let view = if level_size.x / level_size.y > window_ratio {
+        Vec2::new(level_size.y * window_ratio, level_size.y)
+    } else {
+        Vec2::new(level_size.x, level_size.x / window_ratio)
+    };
+    projection.scaling_mode = ScalingMode::Fixed {
+        width: view.x,
+        height: view.y,
+    };
+
+    let player_position = player_transform.translation.truncate();
+    let snap = respawned || player.is_added() || camera.level.as_ref() != Some(level_iid);
+    camera.level = Some(level_iid.clone());
+
+    if snap {
+        camera.focus = player_position;
+        camera.velocity = Vec2::ZERO;
+        camera.look_ahead = 0.;
+        camera.look_ahead_velocity = 0.;
+    }
+
+    // only follow once the player pushes against the edge of the dead-zone
+    let offset = player_position - camera.focus;
+    camera.focus += offset - offset.clamp(-settings.dead_zone, settings.dead_zone);
+
+    let heading = output.map_or(0., |output| output.effective_translation.x);
+    let look_ahead_target = if heading.abs() > f32::EPSILON {
+        heading.signum() * settings.look_ahead
+    } else {
+        0.
+    };
+    let GameCamera {
+        look_ahead,
+        look_ahead_velocity,
+        ..
+    } = camera.as_mut();
+    *look_ahead = smooth_damp(
+        *look_ahead,
+        look_ahead_target,
+        look_ahead_velocity,
+        settings.smooth_time * 2.,
+        time.delta_seconds(),
+    );
+
+    let level_min = level_transform.translation.truncate();
+    let level_max = level_min + level_size;
+    // the endless tower continues above the level
+    let max_y = if endless_tower.is_some() {
+        f32::INFINITY
+    } else {
+        level_max.y - view.y / 2.
+    };
+    let target = Vec2::new(
+        clamp_or_center(
+            camera.focus.x + camera.look_ahead,
+            level_min.x + view.x / 2.,
+            level_max.x - view.x / 2.,
+        ),
+        clamp_or_center(camera.focus.y, level_min.y + view.y / 2., max_y),
+    );
+
+    let current = camera_transform.translation.truncate();
+    let mut position = if snap {
+        target
+    } else {
+        let GameCamera { velocity, .. } = camera.as_mut();
+        Vec2::new(
+            smooth_damp(
+                current.x,
+                target.x,
+                &mut velocity.x,
+                settings.smooth_time,
+                time.delta_seconds(),
+            ),
+            smooth_damp(
+                current.y,
+                target.y,
+                &mut velocity.y,
+                settings.smooth_time,
+                time.delta_seconds(),
+            ),
+        )
+    };
+
+    if settings.never_scroll_down && !snap && position.y < current.y {
+        position.y = current.y;
+        camera.velocity.y = 0.;
+    }
+
+    camera_transform.translation.x = position.x;
+    camera_transform.translation.y = position.y;
+}
+
+#[allow(clippy::type_complexity)]
+fn fall_off_screen(
+    mut death_events: EventWriter<PlayerDied>,
+    settings: Res<CameraSettings>,
+    camera_query: Query<(&Transform, &OrthographicProjection), With<GameCamera>>,
+    player_query: Query<(&Transform, &Collider), (With<Player>, Without<Dying>)>,
+) {
+    if !settings.never_scroll_down {
+        return;
+    }
+    let Ok((camera_transform, projection)) = camera_query.get_single() else {
+        return;
+    };
+    let screen_bottom = camera_transform.translation.y + projection.area.min.y;
+
+    for (player_transform, collider) in &player_query {
+        let half_height = collider.as_cuboid().map_or(0., |cuboid| cuboid.half_extents().y);
+        if player_transform.translation.y + half_height < screen_bottom {
+            death_events.send(PlayerDied {
+                cause: DeathCause::FellOffScreen,
+            });
+        }
+    }
+}
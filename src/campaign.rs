@@ -0,0 +1,213 @@
+/*
+    Campaign progression from one level to the next.
+
+    Reaching a `Goal` switches to `LevelComplete` for a moment and moves on to
+    the level named by the goal's `next_level` field, or the next level in
+    LDtk world order. After the last level the game is over. Going back to the
+    title screen starts a new campaign from the first level.
+
+    Every level has its own `Player` entity, so the player is despawned when
+    leaving a level and the wallet is handed over to the next level's player.
+*/
+
+use crate::coin::Wallet;
+use crate::events::{LevelCompleted, RestartLevelEvent, RestartScope};
+use crate::game_state::{spawn_banner, GameState};
+use crate::player_components::Player;
+use crate::respawn::store_spawn_point;
+use bevy::prelude::*;
+use bevy_ecs_ldtk::ldtk::Level;
+use bevy_ecs_ldtk::prelude::*;
+
+pub struct CampaignPlugin;
+
+impl Plugin for CampaignPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<Campaign>()
+            .add_systems(
+                Update,
+                (
+                    select_level_by_iid,
+                    complete_level.run_if(in_state(GameState::Playing)),
+                    advance_level.run_if(in_state(GameState::LevelComplete)),
+                    carry_wallet.before(store_spawn_point),
+                )
+                    .chain(),
+            )
+            .add_systems(OnEnter(GameState::Title), new_campaign)
+            .add_systems(
+                OnTransition {
+                    from: GameState::Title,
+                    to: GameState::Playing,
+                },
+                start_campaign,
+            )
+            .add_systems(OnEnter(GameState::LevelComplete), show_level_complete)
+            .add_systems(OnEnter(GameState::GameOver), show_game_over);
+    }
+}
+
+/// How long the level-complete banner shows before the next level loads.
+const LEVEL_COMPLETE_TIME: f32 = 1.5;
+
+#[derive(Resource, Default)]
+pub struct Campaign {
+    /// Set once the player starts playing from the title screen.
+    started: bool,
+    /// Iid of the level to load after `LevelComplete`, `None` when it was the last one.
+    next_level: Option<String>,
+    transition: Timer,
+    /// The wallet to hand over to the next level's player.
+    wallet: Option<Wallet>,
+    /// Set once the last level has been completed.
+    pub complete: bool,
+}
+
+/// The level after `current`, either the one called `next_level` or the next one in world order.
+fn next_level<'a>(
+    ldtk_project: &'a LdtkProject,
+    current: &Level,
+    next_level: Option<&str>,
+) -> Option<&'a Level> {
+    if let Some(identifier) = next_level {
+        let level = ldtk_project
+            .iter_raw_levels()
+            .find(|level| level.identifier == identifier);
+        if level.is_some() {
+            return level;
+        }
+        warn!("goal points at level {identifier:?}, which doesn't exist, using world order");
+    }
+
+    ldtk_project
+        .iter_raw_levels()
+        .skip_while(|level| level.iid != current.iid)
+        .nth(1)
+}
+
+// Everything else compares levels by iid, so pin the selection to one as soon as
+// the project has loaded.
+fn select_level_by_iid(
+    mut level_selection: ResMut<LevelSelection>,
+    ldtk_projects: Query<&Handle<LdtkProject>>,
+    ldtk_project_assets: Res<Assets<LdtkProject>>,
+) {
+    if let LevelSelection::Iid(_) = *level_selection {
+        return;
+    }
+
+    let Some(level) = ldtk_projects
+        .get_single()
+        .ok()
+        .and_then(|handle| ldtk_project_assets.get(handle))
+        .and_then(|ldtk_project| ldtk_project.find_raw_level_by_level_selection(&level_selection))
+    else {
+        return;
+    };
+
+    *level_selection = LevelSelection::iid(level.iid.clone());
+}
+
+fn new_campaign(
+    mut campaign: ResMut<Campaign>,
+    mut level_selection: ResMut<LevelSelection>,
+    mut restart_level_events: EventWriter<RestartLevelEvent>,
+) {
+    // the world is already fresh when the game boots into the title screen
+    if !campaign.started {
+        return;
+    }
+
+    *campaign = Campaign::default();
+    *level_selection = LevelSelection::index(0);
+    restart_level_events.send(RestartLevelEvent {
+        scope: RestartScope::World,
+    });
+}
+
+fn start_campaign(mut campaign: ResMut<Campaign>) {
+    campaign.started = true;
+}
+
+fn show_level_complete(mut commands: Commands) {
+    spawn_banner(&mut commands, "Level complete!", "");
+}
+
+fn show_game_over(mut commands: Commands, campaign: Res<Campaign>) {
+    let title = if campaign.complete {
+        "Campaign complete!"
+    } else {
+        "Game over"
+    };
+    spawn_banner(&mut commands, title, "Press Enter / Start");
+}
+
+fn complete_level(
+    mut level_completed_events: EventReader<LevelCompleted>,
+    mut campaign: ResMut<Campaign>,
+    mut next_state: ResMut<NextState<GameState>>,
+    level_selection: Res<LevelSelection>,
+    ldtk_projects: Query<&Handle<LdtkProject>>,
+    ldtk_project_assets: Res<Assets<LdtkProject>>,
+) {
+    let Some(completed) = level_completed_events.read().last() else {
+        return;
+    };
+
+    let Some(ldtk_project) = ldtk_projects
+        .get_single()
+        .ok()
+        .and_then(|handle| ldtk_project_assets.get(handle))
+    else {
+        return;
+    };
+    let Some(current) = ldtk_project.find_raw_level_by_level_selection(&level_selection) else {
+        return;
+    };
+
+    let next_level = next_level(ldtk_project, current, completed.next_level.as_deref());
+    info!(
+        "completed level {}, next up: {:?}",
+        current.identifier,
+        next_level.map(|level| &level.identifier)
+    );
+
+    campaign.next_level = next_level.map(|level| level.iid.clone());
+    campaign.transition = Timer::from_seconds(LEVEL_COMPLETE_TIME, TimerMode::Once);
+    next_state.set(GameState::LevelComplete);
+}
+
+fn advance_level(
+    mut commands: Commands,
+    mut campaign: ResMut<Campaign>,
+    mut level_selection: ResMut<LevelSelection>,
+    mut next_state: ResMut<NextState<GameState>>,
+    players: Query<(Entity, &Wallet), With<Player>>,
+    time: Res<Time>,
+) {
+    if !campaign.transition.tick(time.delta()).just_finished() {
+        return;
+    }
+
+    let Some(next_level) = campaign.next_level.take() else {
+        info!("campaign complete");
+        campaign.complete = true;
+        next_state.set(GameState::GameOver);
+        return;
+    };
+
+    for (player, wallet) in &players {
+        campaign.wallet = Some(wallet.clone());
+        commands.entity(player).despawn_recursive();
+    }
+    *level_selection = LevelSelection::iid(next_level);
+    next_state.set(GameState::Playing);
+}
+
+fn carry_wallet(mut campaign: ResMut<Campaign>, mut players: Query<&mut Wallet, Added<Player>>) {
+    for mut wallet in &mut players {
+        if let Some(carried) = campaign.wallet.take() {
+            *wallet = carried;
+        }
+    }
+}
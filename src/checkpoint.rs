@@ -0,0 +1,136 @@
+/*
+    Mid-level checkpoints.
+
+    Touching a `Checkpoint` flag remembers where the player is and what is in
+    their wallet. Dying restarts the level from the last flag touched, with
+    the coins collected before it still collected.
+*/
+
+use crate::coin::Wallet;
+use crate::game_state::GameState;
+use crate::player_components::Player;
+use bevy::prelude::*;
+use bevy_ecs_ldtk::prelude::*;
+
+pub struct CheckpointPlugin;
+
+impl Plugin for CheckpointPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<FlagFrames>()
+            .register_ldtk_entity::<CheckpointBundle>("Checkpoint")
+            .add_systems(
+                Update,
+                (
+                    restore_active_checkpoint,
+                    touch_checkpoints.run_if(in_state(GameState::Playing)),
+                    wave_flags,
+                )
+                    .chain(),
+            );
+    }
+}
+
+const FLAG_FRAMES: [&str; 4] = [
+    "2D Pixel Dungeon Asset Pack/items and trap_animation/flag/flag_1.png",
+    "2D Pixel Dungeon Asset Pack/items and trap_animation/flag/flag_2.png",
+    "2D Pixel Dungeon Asset Pack/items and trap_animation/flag/flag_3.png",
+    "2D Pixel Dungeon Asset Pack/items and trap_animation/flag/flag_4.png",
+];
+const FLAG_FPS: f32 = 8.;
+const CHECKPOINT_DISTANCE: f32 = 12.0;
+
+#[derive(Clone, Copy, Default, Debug, Component)]
+pub struct Checkpoint {
+    active: bool,
+}
+
+#[derive(Clone, Default, Bundle, LdtkEntity)]
+pub struct CheckpointBundle {
+    #[sprite_bundle("2D Pixel Dungeon Asset Pack/items and trap_animation/flag/flag_1.png")]
+    sprite_bundle: SpriteBundle,
+    checkpoint: Checkpoint,
+}
+
+/// The waving flag animation, loaded once for every checkpoint to share.
+#[derive(Resource)]
+struct FlagFrames([Handle<Image>; FLAG_FRAMES.len()]);
+
+impl FromWorld for FlagFrames {
+    fn from_world(world: &mut World) -> Self {
+        let asset_server = world.resource::<AssetServer>();
+        Self(FLAG_FRAMES.map(|path| asset_server.load(path)))
+    }
+}
+
+/// What the player had when they last touched a checkpoint.
+#[derive(Clone, Component)]
+pub struct LastCheckpoint {
+    pub checkpoint: EntityIid,
+    pub transform: Transform,
+    pub wallet: Wallet,
+}
+
+// Checkpoints respawn with their level, so light the active one up again.
+fn restore_active_checkpoint(
+    mut checkpoints: Query<(&mut Checkpoint, &EntityIid), Added<Checkpoint>>,
+    players: Query<&LastCheckpoint, With<Player>>,
+) {
+    for (mut checkpoint, iid) in &mut checkpoints {
+        checkpoint.active = players
+            .iter()
+            .any(|last_checkpoint| last_checkpoint.checkpoint == *iid);
+    }
+}
+
+fn touch_checkpoints(
+    mut commands: Commands,
+    players: Query<(Entity, &Transform, &Wallet), With<Player>>,
+    mut checkpoints: Query<(&mut Checkpoint, &EntityIid, &GlobalTransform, &mut Handle<Image>)>,
+    flag_frames: Res<FlagFrames>,
+) {
+    for (player, player_transform, wallet) in &players {
+        let touched = checkpoints.iter().find_map(|(checkpoint, iid, transform, _)| {
+            let distance = player_transform
+                .translation
+                .truncate()
+                .distance(transform.translation().truncate());
+            (!checkpoint.active && distance < CHECKPOINT_DISTANCE)
+                .then_some((iid.clone(), transform.translation()))
+        });
+
+        let Some((touched_iid, checkpoint_translation)) = touched else {
+            continue;
+        };
+
+        // the player is worldly, so their transform is already in world space
+        let mut transform = *player_transform;
+        transform.translation.x = checkpoint_translation.x;
+
+        commands.entity(player).insert(LastCheckpoint {
+            checkpoint: touched_iid.clone(),
+            transform,
+            wallet: wallet.clone(),
+        });
+
+        for (mut checkpoint, iid, _, mut texture) in &mut checkpoints {
+            checkpoint.active = *iid == touched_iid;
+            if !checkpoint.active {
+                *texture = flag_frames.0[0].clone();
+            }
+        }
+    }
+}
+
+fn wave_flags(
+    mut checkpoints: Query<(&Checkpoint, &mut Handle<Image>)>,
+    flag_frames: Res<FlagFrames>,
+    time: Res<Time>,
+) {
+    let frame = &flag_frames.0[(time.elapsed_seconds() * FLAG_FPS) as usize % FLAG_FRAMES.len()];
+
+    for (checkpoint, mut texture) in &mut checkpoints {
+        if checkpoint.active && *texture != *frame {
+            *texture = frame.clone();
+        }
+    }
+}
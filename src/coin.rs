@@ -1,11 +1,23 @@
 use bevy::prelude::*;
 use bevy_ecs_ldtk::prelude::*;
+use crate::events::CoinsChanged;
+use crate::game_state::GameState;
+
+use std::collections::HashSet;
 
 pub struct CoinPlugin;
 
 impl Plugin for CoinPlugin {
     fn build(&self, app: &mut App) {
-        app.add_systems(Update, collect)
+        app.add_systems(
+            Update,
+            (
+                remove_collected_coins,
+                collect.run_if(in_state(GameState::Playing)),
+                announce_coins,
+            )
+                .chain(),
+        )
            .register_ldtk_entity::<CoinBundle>("Coin");
     }
 }
@@ -22,9 +34,26 @@ pub struct CoinBundle {
 }
 
 
-#[derive(Default, Component)]
+#[derive(Clone, Default, Component)]
 pub struct Wallet {
     coins: u32,
+    /// Coins picked up so far, so they stay gone when their level respawns.
+    collected: HashSet<EntityIid>,
+}
+
+impl Wallet {
+    pub fn coins(&self) -> u32 {
+        self.coins
+    }
+
+    /// Takes `coins` out of the wallet, if there are enough of them.
+    pub fn spend(&mut self, coins: u32) -> bool {
+        if self.coins < coins {
+            return false;
+        }
+        self.coins -= coins;
+        true
+    }
 }
 
 
@@ -34,10 +63,10 @@ const COLLECT_DISATANCE: f32 = 10.0;
 fn collect(
     mut commands: Commands,
     mut wallets: Query<(&mut Wallet, &GlobalTransform)>,
-    coins: Query<(Entity, &GlobalTransform), With<Coin>>,
+    coins: Query<(Entity, &GlobalTransform, Option<&EntityIid>), With<Coin>>,
 ) {
     for (mut wallet, wallet_transform) in wallets.iter_mut() {
-        for (coin_entity, coin_transform) in coins.iter() {
+        for (coin_entity, coin_transform, coin_iid) in coins.iter() {
             if coin_transform.translation() == Vec3::ZERO {
                 continue;
             }
@@ -45,9 +74,35 @@ fn collect(
             let distance = wallet_transform.translation().distance(coin_transform.translation());
             if distance < COLLECT_DISATANCE {
                 wallet.coins += 1;
-                println!("Collected a coin! Total: {}", wallet.coins);
+                if let Some(coin_iid) = coin_iid {
+                    wallet.collected.insert(coin_iid.clone());
+                }
                 commands.entity(coin_entity).despawn_recursive();
             }
         }
     }
 }
+
+fn remove_collected_coins(
+    mut commands: Commands,
+    wallets: Query<&Wallet>,
+    coins: Query<(Entity, &EntityIid), Added<Coin>>,
+) {
+    for (coin_entity, coin_iid) in &coins {
+        if wallets.iter().any(|wallet| wallet.collected.contains(coin_iid)) {
+            commands.entity(coin_entity).despawn_recursive();
+        }
+    }
+}
+
+// Also catches wallets being restored by a respawn or carried into the next level.
+fn announce_coins(
+    mut coins_changed: EventWriter<CoinsChanged>,
+    wallets: Query<&Wallet, Changed<Wallet>>,
+) {
+    for wallet in &wallets {
+        coins_changed.send(CoinsChanged {
+            total: wallet.coins,
+        });
+    }
+}
@@ -1,9 +1,6 @@
 use bevy::prelude::*;
 use bevy_rapier2d::prelude::*;
-use bevy_ecs_ldtk::{
-    prelude::*,
-    utils::ldtk_pixel_coords_to_translation_pivoted,
-};
+use bevy_ecs_ldtk::prelude::*;
 
 
 use std::collections::HashSet;
@@ -26,6 +23,7 @@ pub struct SensorBundle {
     pub sensor: Sensor,
     pub rotation_constraints: LockedAxes,
     pub active_events: ActiveEvents,
+    pub active_collision_types: ActiveCollisionTypes,
 }
 
 #[derive(Copy, Clone, Eq, PartialEq, Debug, Default, Component)]
@@ -36,6 +34,16 @@ pub struct WallBundle {
     wall: Wall,
 }
 
+/// A platform that can be jumped through from below and the sides, and only
+/// landed on from above.
+#[derive(Copy, Clone, Eq, PartialEq, Debug, Default, Component)]
+pub struct OneWayPlatform;
+
+#[derive(Clone, Debug, Default, Bundle, LdtkIntCell)]
+pub struct OneWayPlatformBundle {
+    one_way_platform: OneWayPlatform,
+}
+
 
 #[derive(Component)]
 pub struct GroundSensor {
@@ -47,6 +55,30 @@ pub struct GroundDetection {
     pub on_ground: bool,
 }
 
+#[derive(Component)]
+pub struct WallSensor {
+    pub wall_detection_entity: Entity,
+    /// -1 for the sensor on the left side, 1 for the one on the right.
+    pub side: f32,
+    pub intersecting_wall_entities: HashSet<Entity>,
+}
+#[derive(Clone, Default, Component)]
+pub struct WallDetection {
+    pub on_wall_left: bool,
+    pub on_wall_right: bool,
+}
+
+impl WallDetection {
+    /// -1 when only touching a wall on the left, 1 when only touching one on the right.
+    pub fn side(&self) -> Option<f32> {
+        match (self.on_wall_left, self.on_wall_right) {
+            (true, false) => Some(-1.),
+            (false, true) => Some(1.),
+            _ => None,
+        }
+    }
+}
+
 impl From<&EntityInstance> for ColliderBundle {
     fn from(entity_instance: &EntityInstance) -> ColliderBundle {
         let rotation_constraints = LockedAxes::ROTATION_LOCKED;
@@ -68,6 +100,32 @@ impl From<&EntityInstance> for ColliderBundle {
                 density: ColliderMassProperties::Density(0.0),
                 ..Default::default()
             },
+            "Spring" => ColliderBundle {
+                collider: Collider::cuboid(8., 8.),
+                rigid_body: RigidBody::Fixed,
+                rotation_constraints,
+                ..Default::default()
+            },
+            "CrumblingPlatform" => ColliderBundle {
+                collider: Collider::cuboid(
+                    entity_instance.width as f32 / 2.,
+                    entity_instance.height as f32 / 2.,
+                ),
+                rigid_body: RigidBody::Fixed,
+                rotation_constraints,
+                friction: Friction::new(1.0),
+                ..Default::default()
+            },
+            "MovingPlatform" => ColliderBundle {
+                collider: Collider::cuboid(
+                    entity_instance.width as f32 / 2.,
+                    entity_instance.height as f32 / 2.,
+                ),
+                rigid_body: RigidBody::KinematicPositionBased,
+                rotation_constraints,
+                friction: Friction::new(1.0),
+                ..Default::default()
+            },
             _ => ColliderBundle::default(),
         }
     }
@@ -75,6 +133,25 @@ impl From<&EntityInstance> for ColliderBundle {
 
 
 
+impl From<&EntityInstance> for SensorBundle {
+    fn from(entity_instance: &EntityInstance) -> SensorBundle {
+        match entity_instance.identifier.as_ref() {
+            "KillZone" => SensorBundle {
+                collider: Collider::cuboid(
+                    entity_instance.width as f32 / 2.,
+                    entity_instance.height as f32 / 2.,
+                ),
+                active_events: ActiveEvents::COLLISION_EVENTS,
+                // the player is kinematic, which rapier doesn't check against fixed colliders by default
+                active_collision_types: ActiveCollisionTypes::default()
+                    | ActiveCollisionTypes::KINEMATIC_STATIC,
+                ..Default::default()
+            },
+            _ => SensorBundle::default(),
+        }
+    }
+}
+
 impl From<IntGridCell> for SensorBundle {
     fn from(_: IntGridCell) -> SensorBundle {
         SensorBundle::default()
@@ -0,0 +1,331 @@
+/*
+    Controls screen for rebinding actions to other keys and gamepad buttons.
+
+    Up / Down pick an action and Jump starts listening: the next key or
+    gamepad button pressed replaces the action's binding on that device,
+    Escape cancels. An input that already belongs to another action is
+    swapped with it, so one press never triggers two actions. Menus goes back
+    to the title screen. The bindings are stored in the save, which hands
+    them to the player.
+*/
+
+use crate::actions::{default_input_map, PlatformerAction};
+use crate::game_state::{just_pressed, Banner, GameState};
+use crate::player_components::Player;
+use crate::save::SaveData;
+use bevy::prelude::*;
+use leafwing_input_manager::prelude::*;
+use leafwing_input_manager::user_input::InputKind;
+
+pub struct ControlsPlugin;
+
+impl Plugin for ControlsPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<ControlsMenu>()
+            .add_systems(
+                OnEnter(GameState::Controls),
+                (reset_menu, show_controls).chain(),
+            )
+            .add_systems(
+                Update,
+                (controls_input, update_rows)
+                    .chain()
+                    .run_if(in_state(GameState::Controls)),
+            );
+    }
+}
+
+/// The actions that can be rebound, in the order they are listed.
+const REBINDABLE: [PlatformerAction; 9] = [
+    PlatformerAction::Left,
+    PlatformerAction::Right,
+    PlatformerAction::Up,
+    PlatformerAction::Down,
+    PlatformerAction::Jump,
+    PlatformerAction::Dash,
+    PlatformerAction::Heal,
+    PlatformerAction::Pause,
+    PlatformerAction::Menus,
+];
+
+#[derive(Resource, Default)]
+struct ControlsMenu {
+    /// Index into `REBINDABLE`.
+    selected: usize,
+    /// Waiting for the next key or button to bind to the selected action.
+    listening: bool,
+    /// Waiting for everything to be let go after rebinding, so the press that
+    /// was just bound doesn't also trigger its new action in the menu.
+    waiting_for_release: bool,
+    message: String,
+}
+
+#[derive(Component)]
+struct BindingRow(usize);
+
+#[derive(Component)]
+struct ControlsMessage;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Device {
+    Keyboard,
+    Gamepad,
+}
+
+impl Device {
+    fn of(input: &UserInput) -> Option<Self> {
+        match input {
+            UserInput::Single(InputKind::Keyboard(_)) => Some(Device::Keyboard),
+            UserInput::Single(InputKind::GamepadButton(_)) => Some(Device::Gamepad),
+            _ => None,
+        }
+    }
+}
+
+/// The bindings currently in use, the saved ones or the defaults.
+fn current_bindings(save_data: &SaveData) -> InputMap<PlatformerAction> {
+    save_data
+        .settings
+        .key_bindings
+        .clone()
+        .unwrap_or_else(default_input_map)
+}
+
+fn bindings_on(
+    input_map: &InputMap<PlatformerAction>,
+    action: PlatformerAction,
+    device: Device,
+) -> Vec<UserInput> {
+    input_map
+        .get(action)
+        .into_iter()
+        .flatten()
+        .filter(|input| Device::of(input) == Some(device))
+        .cloned()
+        .collect()
+}
+
+/// Replaces whatever `action` is bound to on `device` with `input`, returning the first old binding.
+fn replace_binding(
+    input_map: &mut InputMap<PlatformerAction>,
+    action: PlatformerAction,
+    device: Device,
+    input: Option<UserInput>,
+) -> Option<UserInput> {
+    let bindings = input_map.get(action).cloned().unwrap_or_default();
+    let (previous, kept): (Vec<_>, Vec<_>) = bindings
+        .into_iter()
+        .partition(|binding| Device::of(binding) == Some(device));
+
+    input_map.clear_action(action);
+    for binding in kept.into_iter().chain(input) {
+        input_map.insert(binding, action);
+    }
+    previous.into_iter().next()
+}
+
+/// Binds `input` to `action`, handing the action's old binding to any other
+/// action `input` was taken from. Returns a description of what changed.
+fn rebind(
+    input_map: &mut InputMap<PlatformerAction>,
+    action: PlatformerAction,
+    input: UserInput,
+) -> String {
+    let Some(device) = Device::of(&input) else {
+        return format!("{input} can't be bound");
+    };
+
+    let previous = replace_binding(input_map, action, device, Some(input.clone()));
+    let conflict = REBINDABLE.into_iter().find(|&other| {
+        other != action
+            && input_map
+                .get(other)
+                .is_some_and(|bindings| bindings.contains(&input))
+    });
+
+    let Some(other) = conflict else {
+        return format!("{action:?} is now bound to {input}");
+    };
+    replace_binding(input_map, other, device, previous.clone());
+    match previous {
+        Some(previous) => format!("{input} was taken from {other:?}, which now uses {previous}"),
+        None => format!("{input} was taken from {other:?}, which is now unbound"),
+    }
+}
+
+fn reset_menu(mut menu: ResMut<ControlsMenu>) {
+    *menu = ControlsMenu::default();
+}
+
+fn show_controls(mut commands: Commands) {
+    let text = |value: &str, font_size: f32| {
+        TextBundle::from_section(
+            value,
+            TextStyle {
+                font_size,
+                color: Color::WHITE,
+                ..Default::default()
+            },
+        )
+    };
+
+    // a banner, so leaving the state cleans it up like every other screen
+    commands
+        .spawn(NodeBundle {
+            style: Style {
+                width: Val::Percent(100.),
+                height: Val::Percent(100.),
+                flex_direction: FlexDirection::Column,
+                justify_content: JustifyContent::Center,
+                align_items: AlignItems::Center,
+                ..Default::default()
+            },
+            ..Default::default()
+        })
+        .insert(Banner)
+        .with_children(|parent| {
+            parent.spawn(text("Controls", 48.));
+            for index in 0..REBINDABLE.len() {
+                parent.spawn(text("", 24.)).insert(BindingRow(index));
+            }
+            parent.spawn(text("", 24.)).insert(ControlsMessage);
+            parent.spawn(text(
+                "Up / Down to pick, Jump to rebind, Menus to go back",
+                24.,
+            ));
+        });
+}
+
+fn controls_input(
+    mut menu: ResMut<ControlsMenu>,
+    mut save_data: ResMut<SaveData>,
+    mut next_state: ResMut<NextState<GameState>>,
+    players: Query<&ActionState<PlatformerAction>, With<Player>>,
+    keys: Res<Input<KeyCode>>,
+    gamepad_buttons: Res<Input<GamepadButton>>,
+) {
+    if menu.waiting_for_release {
+        if keys.get_pressed().next().is_none() && gamepad_buttons.get_pressed().next().is_none() {
+            menu.waiting_for_release = false;
+        }
+        return;
+    }
+
+    if menu.listening {
+        if keys.just_pressed(KeyCode::Escape) {
+            menu.listening = false;
+            menu.message.clear();
+            return;
+        }
+
+        let input = keys
+            .get_just_pressed()
+            .next()
+            .map(|&key| UserInput::from(key))
+            .or_else(|| {
+                gamepad_buttons
+                    .get_just_pressed()
+                    .next()
+                    .map(|button| UserInput::from(button.button_type))
+            });
+        let Some(input) = input else {
+            return;
+        };
+
+        let mut input_map = current_bindings(&save_data);
+        menu.message = rebind(&mut input_map, REBINDABLE[menu.selected], input);
+        save_data.settings.key_bindings = Some(input_map);
+        menu.listening = false;
+        menu.waiting_for_release = true;
+        return;
+    }
+
+    if just_pressed(&players, PlatformerAction::Up) {
+        menu.selected = (menu.selected + REBINDABLE.len() - 1) % REBINDABLE.len();
+    } else if just_pressed(&players, PlatformerAction::Down) {
+        menu.selected = (menu.selected + 1) % REBINDABLE.len();
+    } else if just_pressed(&players, PlatformerAction::Jump) {
+        menu.listening = true;
+        menu.message = format!(
+            "Press a key or button for {:?}, Escape to cancel",
+            REBINDABLE[menu.selected]
+        );
+    } else if just_pressed(&players, PlatformerAction::Menus) {
+        next_state.set(GameState::Title);
+    }
+}
+
+fn update_rows(
+    menu: Res<ControlsMenu>,
+    save_data: Res<SaveData>,
+    mut rows: Query<(&mut Text, &BindingRow)>,
+    mut messages: Query<&mut Text, (With<ControlsMessage>, Without<BindingRow>)>,
+) {
+    if !menu.is_changed() && !save_data.is_changed() {
+        return;
+    }
+
+    let input_map = current_bindings(&save_data);
+    let describe = |action, device| {
+        let bindings = bindings_on(&input_map, action, device);
+        if bindings.is_empty() {
+            "-".to_string()
+        } else {
+            bindings
+                .iter()
+                .map(ToString::to_string)
+                .collect::<Vec<_>>()
+                .join(", ")
+        }
+    };
+
+    for (mut text, BindingRow(index)) in &mut rows {
+        let action = REBINDABLE[*index];
+        let marker = if *index == menu.selected { ">" } else { " " };
+        text.sections[0].value = format!(
+            "{marker} {action:?}: {} / {}",
+            describe(action, Device::Keyboard),
+            describe(action, Device::Gamepad)
+        );
+    }
+    for mut text in &mut messages {
+        text.sections[0].value = menu.message.clone();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rebinding_to_a_free_key_keeps_the_gamepad_binding() {
+        let mut input_map = default_input_map();
+
+        rebind(&mut input_map, PlatformerAction::Jump, KeyCode::K.into());
+
+        assert_eq!(
+            bindings_on(&input_map, PlatformerAction::Jump, Device::Keyboard),
+            vec![KeyCode::K.into()]
+        );
+        assert_eq!(
+            bindings_on(&input_map, PlatformerAction::Jump, Device::Gamepad),
+            vec![GamepadButtonType::South.into()]
+        );
+    }
+
+    #[test]
+    fn rebinding_to_a_taken_key_swaps_the_bindings() {
+        let mut input_map = default_input_map();
+
+        rebind(&mut input_map, PlatformerAction::Jump, KeyCode::E.into());
+
+        assert_eq!(
+            bindings_on(&input_map, PlatformerAction::Jump, Device::Keyboard),
+            vec![KeyCode::E.into()]
+        );
+        assert_eq!(
+            bindings_on(&input_map, PlatformerAction::Dash, Device::Keyboard),
+            vec![KeyCode::Space.into()]
+        );
+    }
+}
@@ -0,0 +1,262 @@
+/*
+    Crouching on `PlatformerAction::Down`.
+
+    Holding Down on the ground crouches: the player's collider shrinks to
+    `crouch_height` of its standing height with the feet kept where they
+    are, the ground sensor follows the new bottom of the collider, the wall
+    sensors shrink with it so walls above the player's head don't count, and
+    the player slows down to `crouch_speed`. Letting go of Down only stands the
+    player back up once a shape cast finds room above their head, so they
+    can't stand up into a low ceiling.
+*/
+
+use crate::actions::PlatformerAction;
+use crate::components::{GroundSensor, WallSensor};
+use crate::death::Dying;
+use crate::events::PlayerRespawned;
+use crate::game_state::GameState;
+use crate::player::{change_character_position, Event, PlayerState, State};
+use crate::player_components::{MovementSettings, Player};
+use crate::systems::wall_sensor_shape;
+use bevy::prelude::*;
+use bevy::sprite::Anchor;
+use bevy_rapier2d::prelude::*;
+use leafwing_input_manager::prelude::*;
+
+pub struct CrouchPlugin;
+
+impl Plugin for CrouchPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(
+            Update,
+            (crouch, resize_collider)
+                .chain()
+                .before(change_character_position)
+                .run_if(in_state(GameState::Playing)),
+        );
+    }
+}
+
+/// Present on the player while their collider is shrunk.
+#[derive(Component)]
+pub struct Crouched {
+    /// Half extents of the standing collider.
+    standing: Vec2,
+}
+
+/// Whether the player could grow back to their standing height where they are.
+fn room_to_stand(
+    rapier_context: &RapierContext,
+    player: Entity,
+    transform: &GlobalTransform,
+    collider: &Collider,
+    crouched: Option<&Crouched>,
+) -> bool {
+    let (Some(crouched), Some(cuboid)) = (crouched, collider.as_cuboid()) else {
+        return true;
+    };
+
+    // the feet stay put, so the top of the collider rises twice as far as its center
+    let rise = 2. * (crouched.standing.y - cuboid.half_extents().y);
+    rapier_context
+        .cast_shape(
+            transform.translation().truncate(),
+            0.,
+            Vec2::Y,
+            collider,
+            rise,
+            true,
+            QueryFilter::new()
+                .exclude_sensors()
+                .exclude_collider(player),
+        )
+        .is_none()
+}
+
+#[allow(clippy::type_complexity)]
+fn crouch(
+    rapier_context: Res<RapierContext>,
+    mut players: Query<
+        (
+            Entity,
+            &ActionState<PlatformerAction>,
+            &GlobalTransform,
+            &Collider,
+            &mut PlayerState,
+            Option<&Crouched>,
+        ),
+        (With<Player>, Without<Dying>),
+    >,
+) {
+    for (player, action_state, transform, collider, mut state, crouched) in &mut players {
+        let holding = action_state.pressed(PlatformerAction::Down);
+
+        match state.state() {
+            State::Idle {} if holding => state.handle(&Event::Crouch),
+            State::Crouching {}
+                if !holding
+                    && room_to_stand(&rapier_context, player, transform, collider, crouched) =>
+            {
+                state.handle(&Event::Stand)
+            }
+            _ => {}
+        }
+    }
+}
+
+// Shrinks the collider when crouching starts and grows it back once the player
+// has left the Crouching state and there is room, e.g. after dashing out from
+// under a low ceiling. A respawn always stands the player up where they spawn.
+#[allow(clippy::type_complexity)]
+fn resize_collider(
+    mut commands: Commands,
+    mut respawn_events: EventReader<PlayerRespawned>,
+    rapier_context: Res<RapierContext>,
+    mut players: Query<
+        (
+            Entity,
+            &PlayerState,
+            &MovementSettings,
+            &GlobalTransform,
+            &mut Collider,
+            &mut Transform,
+            &mut TextureAtlasSprite,
+            &Handle<TextureAtlas>,
+            Option<&Crouched>,
+        ),
+        With<Player>,
+    >,
+    mut ground_sensors: Query<(&GroundSensor, &mut Transform), Without<Player>>,
+    mut wall_sensors: Query<(&WallSensor, &mut Collider), Without<Player>>,
+    texture_atlases: Res<Assets<TextureAtlas>>,
+) {
+    let respawned = respawn_events.read().count() > 0;
+
+    for (
+        player,
+        state,
+        settings,
+        global_transform,
+        mut collider,
+        mut transform,
+        mut sprite,
+        texture_atlas,
+        crouched,
+    ) in &mut players
+    {
+        let Some(half_extents) = collider.as_cuboid().map(|cuboid| cuboid.half_extents()) else {
+            continue;
+        };
+        let crouching = matches!(state.state(), State::Crouching {});
+
+        let (standing, half_height) = match crouched {
+            None if crouching => (half_extents, half_extents.y * settings.crouch_height),
+            Some(crouched) if respawned => (crouched.standing, crouched.standing.y),
+            Some(crouched)
+                if !crouching
+                    && room_to_stand(
+                        &rapier_context,
+                        player,
+                        global_transform,
+                        &collider,
+                        Some(crouched),
+                    ) =>
+            {
+                (crouched.standing, crouched.standing.y)
+            }
+            _ => continue,
+        };
+
+        *collider = Collider::cuboid(half_extents.x, half_height);
+        // a respawn has already put the player back at their standing height
+        if !respawned {
+            transform.translation.y += half_height - half_extents.y;
+        }
+        for (sensor, mut sensor_transform) in &mut ground_sensors {
+            if sensor.ground_detection_entity == player {
+                sensor_transform.translation.y = -half_height;
+            }
+        }
+        for (sensor, mut sensor_collider) in &mut wall_sensors {
+            if sensor.wall_detection_entity == player {
+                *sensor_collider = wall_sensor_shape(half_height);
+            }
+        }
+
+        if crouching && !respawned {
+            // the body moved down under the sprite, so move the sprite back up
+            let sprite_height = texture_atlases
+                .get(texture_atlas)
+                .and_then(|atlas| atlas.textures.get(sprite.index))
+                .map_or(standing.y * 2., |rect| rect.height());
+            let lowered = standing.y - half_height;
+            sprite.anchor = Anchor::Custom(Vec2::new(0., -lowered / sprite_height));
+            commands.entity(player).insert(Crouched { standing });
+        } else {
+            sprite.anchor = Anchor::Center;
+            commands.entity(player).remove::<Crouched>();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashSet;
+    use std::time::Duration;
+
+    fn half_height(app: &App, entity: Entity) -> f32 {
+        let collider = app.world.get::<Collider>(entity).unwrap();
+        collider.as_cuboid().unwrap().half_extents().y
+    }
+
+    #[test]
+    fn wall_sensors_shrink_with_the_collider() {
+        let mut app = App::new();
+        app.add_plugins(MinimalPlugins)
+            .add_event::<PlayerRespawned>()
+            .init_resource::<RapierContext>()
+            .init_resource::<Assets<TextureAtlas>>()
+            .add_systems(Update, resize_collider);
+
+        let mut state = PlayerState::new(Duration::ZERO, Duration::ZERO);
+        state.handle(&Event::Crouch);
+        let player = app
+            .world
+            .spawn((
+                Player,
+                state,
+                MovementSettings {
+                    crouch_height: 0.3,
+                    ..Default::default()
+                },
+                GlobalTransform::default(),
+                Transform::default(),
+                Collider::cuboid(6., 14.),
+                TextureAtlasSprite::default(),
+                Handle::<TextureAtlas>::default(),
+            ))
+            .id();
+        let wall_sensors = [-1., 1.].map(|side| {
+            app.world
+                .spawn((
+                    WallSensor {
+                        wall_detection_entity: player,
+                        side,
+                        intersecting_wall_entities: HashSet::new(),
+                    },
+                    wall_sensor_shape(14.),
+                ))
+                .set_parent(player)
+                .id()
+        });
+
+        app.update();
+
+        let crouched_height = half_height(&app, player);
+        assert!(crouched_height < 14.);
+        for sensor in wall_sensors {
+            assert!(half_height(&app, sensor) <= crouched_height);
+        }
+    }
+}
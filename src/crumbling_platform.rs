@@ -0,0 +1,203 @@
+/*
+    Platforms that crumble shortly after the player lands on them.
+
+    The `CrumblingPlatform` LDtk entity takes a `crumble_delay` in seconds and an
+    optional `respawn_time`; leave it null for platforms that stay broken until
+    the player respawns or the level restarts. A restart only respawns the
+    selected level, so whenever the player respawns, at their spawn point or
+    at a checkpoint, every platform is put back together, including those in
+    neighbouring levels. Reloading the whole world brings them back with it.
+*/
+
+use crate::components::{ColliderBundle, GroundSensor};
+use crate::events::PlayerRespawned;
+use crate::game_state::GameState;
+use crate::player_components::float_field;
+use bevy::prelude::*;
+use bevy_easings::*;
+use bevy_ecs_ldtk::prelude::*;
+use bevy_rapier2d::prelude::*;
+
+use std::time::Duration;
+
+pub struct CrumblingPlatformPlugin;
+
+impl Plugin for CrumblingPlatformPlugin {
+    fn build(&self, app: &mut App) {
+        app.register_ldtk_entity::<CrumblingPlatformBundle>("CrumblingPlatform")
+            .add_systems(
+                Update,
+                (
+                    store_rest_transform,
+                    restore_platforms,
+                    (start_crumbling, crumble)
+                        .chain()
+                        .run_if(in_state(GameState::Playing)),
+                )
+                    .chain(),
+            );
+    }
+}
+
+/// Default `crumble_delay` when the LDtk field is missing.
+const DEFAULT_CRUMBLE_DELAY: f32 = 0.5;
+/// How far the platform shakes from side to side, in pixels.
+const SHAKE_DISTANCE: f32 = 1.;
+/// How many times a second the platform shakes.
+const SHAKE_FREQUENCY: f32 = 30.;
+const BREAK_DURATION: Duration = Duration::from_millis(150);
+const REAPPEAR_DURATION: Duration = Duration::from_millis(250);
+
+#[derive(Clone, Debug, Default)]
+enum Crumble {
+    #[default]
+    Solid,
+    Shaking(Timer),
+    /// Counting down to reappearing, if the platform ever does.
+    Broken(Option<Timer>),
+}
+
+#[derive(Clone, Debug, Component)]
+pub struct CrumblingPlatform {
+    /// Seconds between the player landing and the platform breaking.
+    pub crumble_delay: f32,
+    /// Seconds the platform stays broken, or `None` to stay broken for good.
+    pub respawn_time: Option<f32>,
+    crumble: Crumble,
+    rest: Transform,
+}
+
+impl Default for CrumblingPlatform {
+    fn default() -> Self {
+        CrumblingPlatform {
+            crumble_delay: DEFAULT_CRUMBLE_DELAY,
+            respawn_time: None,
+            crumble: Crumble::default(),
+            rest: Transform::default(),
+        }
+    }
+}
+
+impl From<&EntityInstance> for CrumblingPlatform {
+    fn from(entity_instance: &EntityInstance) -> Self {
+        CrumblingPlatform {
+            crumble_delay: float_field(entity_instance, "crumble_delay", DEFAULT_CRUMBLE_DELAY),
+            respawn_time: entity_instance.get_float_field("respawn_time").ok().copied(),
+            ..Default::default()
+        }
+    }
+}
+
+#[derive(Clone, Default, Bundle, LdtkEntity)]
+pub struct CrumblingPlatformBundle {
+    #[sprite_sheet_bundle]
+    sprite_sheet_bundle: SpriteSheetBundle,
+    #[from_entity_instance]
+    pub collider_bundle: ColliderBundle,
+    #[from_entity_instance]
+    pub crumbling_platform: CrumblingPlatform,
+}
+
+// LDtk positions the platform after the bundle is built, so remember where it sits
+// to shake around and reappear at.
+fn store_rest_transform(
+    mut platforms: Query<(&mut CrumblingPlatform, &Transform), Added<CrumblingPlatform>>,
+) {
+    for (mut platform, transform) in &mut platforms {
+        platform.rest = *transform;
+    }
+}
+
+// Respawning only moves the player back, so put the platforms back together here.
+fn restore_platforms(
+    mut commands: Commands,
+    mut respawn_events: EventReader<PlayerRespawned>,
+    mut platforms: Query<(Entity, &mut CrumblingPlatform, &mut Transform)>,
+) {
+    if respawn_events.read().count() == 0 {
+        return;
+    }
+
+    for (entity, mut platform, mut transform) in &mut platforms {
+        platform.crumble = Crumble::Solid;
+        *transform = platform.rest;
+        commands
+            .entity(entity)
+            .remove::<ColliderDisabled>()
+            .remove::<EasingComponent<Transform>>();
+    }
+}
+
+fn start_crumbling(
+    ground_sensors: Query<&GroundSensor, Changed<GroundSensor>>,
+    mut platforms: Query<&mut CrumblingPlatform>,
+) {
+    for sensor in &ground_sensors {
+        for &ground in &sensor.intersecting_ground_entities {
+            let Ok(mut platform) = platforms.get_mut(ground) else {
+                continue;
+            };
+
+            if let Crumble::Solid = platform.crumble {
+                platform.crumble = Crumble::Shaking(Timer::from_seconds(
+                    platform.crumble_delay,
+                    TimerMode::Once,
+                ));
+            }
+        }
+    }
+}
+
+fn crumble(
+    mut commands: Commands,
+    mut platforms: Query<(Entity, &mut CrumblingPlatform, &mut Transform)>,
+    time: Res<Time>,
+) {
+    for (entity, mut platform, mut transform) in &mut platforms {
+        let rest = platform.rest;
+
+        match &mut platform.crumble {
+            Crumble::Solid => {}
+            Crumble::Shaking(timer) => {
+                timer.tick(time.delta());
+                let shake = (timer.elapsed_secs() * SHAKE_FREQUENCY * std::f32::consts::TAU).sin();
+                transform.translation.x = rest.translation.x + shake * SHAKE_DISTANCE;
+
+                if timer.finished() {
+                    let respawn_timer = platform
+                        .respawn_time
+                        .map(|seconds| Timer::from_seconds(seconds, TimerMode::Once));
+                    platform.crumble = Crumble::Broken(respawn_timer);
+
+                    commands.entity(entity).insert((
+                        ColliderDisabled,
+                        rest.ease_to(
+                            rest.with_scale(Vec3::new(rest.scale.x, 0., rest.scale.z)),
+                            EaseFunction::QuadraticIn,
+                            EasingType::Once {
+                                duration: BREAK_DURATION,
+                            },
+                        ),
+                    ));
+                }
+            }
+            Crumble::Broken(Some(timer)) => {
+                if timer.tick(time.delta()).finished() {
+                    platform.crumble = Crumble::Solid;
+
+                    commands
+                        .entity(entity)
+                        .remove::<ColliderDisabled>()
+                        .insert(rest.with_scale(Vec3::ZERO).ease_to(
+                            rest,
+                            EaseFunction::BackOut,
+                            EasingType::Once {
+                                duration: REAPPEAR_DURATION,
+                            },
+                        ));
+                }
+            }
+            Crumble::Broken(None) => {}
+        }
+    }
+}
@@ -0,0 +1,195 @@
+/*
+    Dash on `PlatformerAction::Dash`.
+
+    A dash covers a fixed distance in a fixed time in the direction held, or
+    the one the player faces, with gravity suspended. Dashes are spaced by a
+    cooldown, and only `air_dashes` of them are allowed before landing again.
+    While dashing the player leaves fading afterimages behind. Everything is
+    tuned through `DashSettings`.
+*/
+
+use crate::actions::PlatformerAction;
+use crate::death::Dying;
+use crate::game_state::GameState;
+use crate::player::{change_character_position, horizontal_input, Event, PlayerState, State};
+use crate::player_components::{
+    DashSettings, DashState, JumpState, MovementSettings, MovementState, Player,
+};
+use bevy::prelude::*;
+use bevy_rapier2d::prelude::*;
+use leafwing_input_manager::prelude::*;
+
+use std::time::Duration;
+
+pub struct DashPlugin;
+
+impl Plugin for DashPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(
+            Update,
+            (
+                (start_dash, dash)
+                    .chain()
+                    .before(change_character_position)
+                    .run_if(in_state(GameState::Playing)),
+                fade_afterimages,
+            ),
+        );
+    }
+}
+
+/// How transparent an afterimage starts out.
+const AFTERIMAGE_ALPHA: f32 = 0.5;
+
+#[derive(Component)]
+pub struct Afterimage(Timer);
+
+#[allow(clippy::type_complexity)]
+fn start_dash(
+    mut players: Query<
+        (
+            &ActionState<PlatformerAction>,
+            &DashSettings,
+            &mut DashState,
+            &mut JumpState,
+            &MovementState,
+            &mut PlayerState,
+        ),
+        (With<Player>, Without<Dying>),
+    >,
+    time: Res<Time>,
+) {
+    let now = time.elapsed();
+
+    for (action_state, settings, mut dash, mut jump, movement, mut state) in &mut players {
+        let grounded = matches!(state.state(), State::Idle {});
+        if grounded {
+            dash.air_dashes_left = settings.air_dashes;
+        }
+
+        if !action_state.just_pressed(PlatformerAction::Dash)
+            || matches!(state.state(), State::Dashing {})
+        {
+            continue;
+        }
+        let cooling_down = dash.started.is_some_and(|started| {
+            now.saturating_sub(started) < Duration::from_secs_f32(settings.dash_cooldown)
+        });
+        if cooling_down || (!grounded && dash.air_dashes_left == 0) {
+            continue;
+        }
+
+        if !grounded {
+            dash.air_dashes_left -= 1;
+        }
+        let input = horizontal_input(action_state);
+        dash.direction = if input != 0. {
+            input.signum()
+        } else {
+            movement.facing
+        };
+        dash.started = Some(now);
+        dash.timer = Timer::from_seconds(settings.dash_duration, TimerMode::Once);
+        dash.afterimage = Timer::from_seconds(settings.afterimage_interval, TimerMode::Repeating);
+
+        jump.velocity = 0.;
+        jump.rising = false;
+        state.handle(&Event::Dash);
+    }
+}
+
+#[allow(clippy::type_complexity)]
+fn dash(
+    mut commands: Commands,
+    mut players: Query<
+        (
+            &KinematicCharacterControllerOutput,
+            &DashSettings,
+            &MovementSettings,
+            &mut DashState,
+            &mut MovementState,
+            &mut JumpState,
+            &mut PlayerState,
+            &GlobalTransform,
+            &Handle<TextureAtlas>,
+            &TextureAtlasSprite,
+        ),
+        (With<Player>, Without<Dying>),
+    >,
+    time: Res<Time>,
+) {
+    for (
+        output,
+        settings,
+        movement_settings,
+        mut dash,
+        mut movement,
+        mut jump,
+        mut state,
+        transform,
+        texture_atlas,
+        sprite,
+    ) in &mut players
+    {
+        if !matches!(state.state(), State::Dashing {}) {
+            continue;
+        }
+
+        // the dash ends a frame after running out, so its last frame still covers
+        // the full distance, and leaves the player at running speed
+        if dash.timer.finished() {
+            movement.velocity =
+                dash.direction * movement_settings.max_speed.min(settings.dash_speed());
+            state.handle(&Event::DashEnd {
+                event_time: time.elapsed(),
+                grounded: output.grounded,
+            });
+            continue;
+        }
+
+        let before = dash.timer.elapsed_secs();
+        let dashed = dash.timer.tick(time.delta()).elapsed_secs() - before;
+        movement.velocity = if time.delta_seconds() > 0. {
+            dash.direction * settings.dash_speed() * dashed / time.delta_seconds()
+        } else {
+            0.
+        };
+        jump.velocity = 0.;
+
+        if dash.afterimage.tick(time.delta()).just_finished() {
+            let mut afterimage_transform = transform.compute_transform();
+            afterimage_transform.translation.z -= 0.1;
+
+            commands
+                .spawn(SpriteSheetBundle {
+                    texture_atlas: texture_atlas.clone(),
+                    sprite: TextureAtlasSprite {
+                        color: sprite.color.with_a(AFTERIMAGE_ALPHA),
+                        ..sprite.clone()
+                    },
+                    transform: afterimage_transform,
+                    ..Default::default()
+                })
+                .insert(Afterimage(Timer::from_seconds(
+                    settings.afterimage_lifetime,
+                    TimerMode::Once,
+                )));
+        }
+    }
+}
+
+fn fade_afterimages(
+    mut commands: Commands,
+    mut afterimages: Query<(Entity, &mut Afterimage, &mut TextureAtlasSprite)>,
+    time: Res<Time>,
+) {
+    for (entity, mut afterimage, mut sprite) in &mut afterimages {
+        if afterimage.0.tick(time.delta()).finished() {
+            commands.entity(entity).despawn_recursive();
+        } else {
+            sprite
+                .color
+                .set_a(AFTERIMAGE_ALPHA * afterimage.0.percent_left());
+        }
+    }
+}
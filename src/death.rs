@@ -0,0 +1,150 @@
+/*
+    Everything that kills the player, and bringing them back afterwards.
+
+    Kill zones send `PlayerDied`. The player then freezes and blinks for a
+    moment before a checkpoint restart puts them back at their last
+    checkpoint, only respawning the level they are in. In endless mode there
+    are no second chances and the game is over instead.
+*/
+
+use crate::components::SensorBundle;
+use crate::endless::EndlessTower;
+use crate::events::{DeathCause, PlayerDied, RestartLevelEvent, RestartScope};
+use crate::game_state::GameState;
+use crate::player_components::Player;
+use bevy::prelude::*;
+use bevy_ecs_ldtk::prelude::*;
+use bevy_rapier2d::prelude::*;
+
+pub struct DeathPlugin;
+
+impl Plugin for DeathPlugin {
+    fn build(&self, app: &mut App) {
+        app.register_ldtk_entity::<KillZoneBundle>("KillZone")
+            .add_systems(
+                Update,
+                (fall_out_of_level, kill_zone_contact, start_dying, dying)
+                    .chain()
+                    .run_if(in_state(GameState::Playing)),
+            );
+    }
+}
+
+/// How long the player stays frozen after dying.
+const DEATH_DURATION: f32 = 0.8;
+/// How many times a second the player blinks while dying.
+const BLINK_FREQUENCY: f32 = 8.;
+const DEATH_COLOR: Color = Color::rgb(1., 0.3, 0.3);
+
+#[derive(Clone, Copy, Default, Debug, Component)]
+pub struct KillZone;
+
+#[derive(Clone, Default, Bundle, LdtkEntity)]
+pub struct KillZoneBundle {
+    #[from_entity_instance]
+    pub sensor_bundle: SensorBundle,
+    pub kill_zone: KillZone,
+}
+
+/// Present on the player during the death sequence.
+#[derive(Component)]
+pub struct Dying(Timer);
+
+#[allow(clippy::type_complexity)]
+fn fall_out_of_level(
+    mut death_events: EventWriter<PlayerDied>,
+    players: Query<(&GlobalTransform, &Collider), (With<Player>, Without<Dying>)>,
+    level_query: Query<(&Transform, &LevelIid)>,
+    ldtk_projects: Query<&Handle<LdtkProject>>,
+    ldtk_project_assets: Res<Assets<LdtkProject>>,
+    level_selection: Res<LevelSelection>,
+) {
+    let Some(ldtk_project) = ldtk_projects
+        .get_single()
+        .ok()
+        .and_then(|handle| ldtk_project_assets.get(handle))
+    else {
+        return;
+    };
+
+    let Some(level_bottom) = level_query.iter().find_map(|(level_transform, level_iid)| {
+        let level = ldtk_project.get_raw_level_by_iid(&level_iid.to_string())?;
+        level_selection
+            .is_match(&LevelIndices::default(), level)
+            .then_some(level_transform.translation.y)
+    }) else {
+        return;
+    };
+
+    for (transform, collider) in &players {
+        let half_height = collider.as_cuboid().map_or(0., |cuboid| cuboid.half_extents().y);
+        if transform.translation().y + half_height < level_bottom {
+            death_events.send(PlayerDied {
+                cause: DeathCause::OutOfBounds,
+            });
+        }
+    }
+}
+
+fn kill_zone_contact(
+    mut collisions: EventReader<CollisionEvent>,
+    mut death_events: EventWriter<PlayerDied>,
+    players: Query<(), (With<Player>, Without<Dying>)>,
+    kill_zones: Query<(), With<KillZone>>,
+) {
+    for collision in collisions.read() {
+        if let CollisionEvent::Started(e1, e2, _) = collision {
+            if (players.contains(*e1) && kill_zones.contains(*e2))
+                || (players.contains(*e2) && kill_zones.contains(*e1))
+            {
+                death_events.send(PlayerDied {
+                    cause: DeathCause::KillZone,
+                });
+            }
+        }
+    }
+}
+
+fn start_dying(
+    mut commands: Commands,
+    mut death_events: EventReader<PlayerDied>,
+    players: Query<Entity, (With<Player>, Without<Dying>)>,
+) {
+    // several kill zones can catch the player in the same frame, one death is enough
+    let Some(death) = death_events.read().last() else {
+        return;
+    };
+
+    for player in &players {
+        info!("player died: {:?}", death.cause);
+        commands
+            .entity(player)
+            .insert(Dying(Timer::from_seconds(DEATH_DURATION, TimerMode::Once)));
+    }
+}
+
+fn dying(
+    mut restart_level_events: EventWriter<RestartLevelEvent>,
+    mut next_state: ResMut<NextState<GameState>>,
+    endless_tower: Option<Res<EndlessTower>>,
+    mut players: Query<(&mut Dying, &mut TextureAtlasSprite)>,
+    time: Res<Time>,
+) {
+    for (mut dying, mut sprite) in &mut players {
+        let blink = (dying.0.elapsed_secs() * BLINK_FREQUENCY).fract() < 0.5;
+        sprite.color = if blink { DEATH_COLOR } else { Color::WHITE };
+
+        // the restart takes the player out of the death sequence
+        if !dying.0.tick(time.delta()).just_finished() {
+            continue;
+        }
+
+        if endless_tower.is_some() {
+            next_state.set(GameState::GameOver);
+        } else {
+            restart_level_events.send(RestartLevelEvent {
+                scope: RestartScope::Checkpoint,
+            });
+        }
+    }
+}
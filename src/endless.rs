@@ -0,0 +1,481 @@
+/*
+    Endless mode: an infinite tower of procedurally generated platform rows
+    stacked on top of the current level, in the spirit of Doodle Jump.
+
+    Rows are generated from the "endless" stream of the GameRng so the same
+    seed always builds the same tower, and are despawned again once they drop
+    below the camera.
+*/
+
+use crate::components::{OneWayPlatform, Wall};
+use crate::death::Dying;
+use crate::events::{DamageEvent, DeathCause, PlayerRespawned, RestartLevelEvent};
+use crate::game_state::GameState;
+use crate::player_components::Player;
+use crate::rng::GameRng;
+use crate::systems::{merge_wall_rects, spawn_wall_rects};
+use crate::wrap::{level_wraps, seam_rects};
+use bevy::prelude::*;
+use bevy_ecs_ldtk::prelude::*;
+use rand::prelude::*;
+use rand_chacha::ChaCha8Rng;
+
+use std::collections::HashSet;
+
+pub struct EndlessPlugin;
+
+impl Plugin for EndlessPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<EndlessSettings>().add_systems(
+            Update,
+            (
+                reset_tower,
+                start_tower,
+                (generate_rows, despawn_rows, hazard_contact)
+                    .chain()
+                    .run_if(in_state(GameState::Playing)),
+            )
+                .chain()
+                .run_if(resource_exists::<EndlessTower>()),
+        );
+    }
+}
+
+const TILESET_PATH: &str = "2D Pixel Dungeon Asset Pack/character and tileset/Dungeon_Tileset.png";
+const HAZARD_PATH: &str = "2D Pixel Dungeon Asset Pack/items and trap_animation/peaks/peaks_4.png";
+const PLATFORM_TILE: usize = 44;
+const SIDE_WALL_TILE: usize = 15;
+const HAZARD_DISTANCE: f32 = 12.0;
+
+/// Difficulty curve of the tower. Everything ramps linearly from the `min_`
+/// value at the bottom of the tower to the `max_` value at `difficulty_height`.
+#[derive(Resource, Clone, Debug)]
+pub struct EndlessSettings {
+    /// Height in pixels above the tower base where the difficulty peaks.
+    pub difficulty_height: f32,
+    /// Vertical distance between rows, in tiles.
+    pub min_row_gap: i32,
+    pub max_row_gap: i32,
+    /// Width of a single platform, in tiles.
+    pub min_platform_width: i32,
+    pub max_platform_width: i32,
+    /// Chance for a platform to carry a hazard.
+    pub min_hazard_chance: f32,
+    pub max_hazard_chance: f32,
+    /// How far above the top of the screen rows are generated, in pixels.
+    pub lookahead: f32,
+}
+
+impl Default for EndlessSettings {
+    fn default() -> Self {
+        Self {
+            difficulty_height: 4000.,
+            min_row_gap: 2,
+            max_row_gap: 3,
+            min_platform_width: 2,
+            max_platform_width: 4,
+            min_hazard_chance: 0.,
+            max_hazard_chance: 0.4,
+            lookahead: 64.,
+        }
+    }
+}
+
+/// Presence of this resource turns on endless mode.
+#[derive(Resource)]
+pub struct EndlessTower {
+    rng: ChaCha8Rng,
+    layout: Option<TowerLayout>,
+}
+
+/// Where the tower sits in the world, decided once the level has spawned.
+struct TowerLayout {
+    origin: Vec2,
+    width: i32,
+    grid_size: i32,
+    wraps: bool,
+    next_row_y: i32,
+    tileset: Handle<TextureAtlas>,
+    hazard: Handle<Image>,
+}
+
+impl EndlessTower {
+    pub fn new(game_rng: &GameRng) -> Self {
+        Self {
+            rng: game_rng.fork("endless"),
+            layout: None,
+        }
+    }
+}
+
+impl FromWorld for EndlessTower {
+    fn from_world(world: &mut World) -> Self {
+        Self::new(world.resource::<GameRng>())
+    }
+}
+
+/// One horizontal band of the tower, with its platforms, side walls and hazards as children.
+#[derive(Component)]
+pub struct TowerRow {
+    /// World height of the top of the band.
+    top: f32,
+}
+
+#[derive(Component)]
+pub struct Hazard;
+
+fn reset_tower(
+    mut commands: Commands,
+    mut restart_level_events: EventReader<RestartLevelEvent>,
+    mut respawn_events: EventReader<PlayerRespawned>,
+    mut tower: ResMut<EndlessTower>,
+    game_rng: Res<GameRng>,
+    rows: Query<Entity, With<TowerRow>>,
+) {
+    // the rows under the player are gone once they die, so build the tower up again
+    let restarted = restart_level_events.read().count() > 0;
+    let respawned = respawn_events.read().count() > 0;
+    if !restarted && !respawned {
+        return;
+    }
+
+    for row in &rows {
+        commands.entity(row).despawn_recursive();
+    }
+    *tower = EndlessTower::new(&game_rng);
+}
+
+// The tower is stacked on top of the selected level, so wait for it to spawn.
+fn start_tower(
+    mut tower: ResMut<EndlessTower>,
+    level_query: Query<(&Transform, &LevelIid)>,
+    ldtk_projects: Query<&Handle<LdtkProject>>,
+    ldtk_project_assets: Res<Assets<LdtkProject>>,
+    level_selection: Res<LevelSelection>,
+    asset_server: Res<AssetServer>,
+    mut texture_atlases: ResMut<Assets<TextureAtlas>>,
+) {
+    if tower.layout.is_some() {
+        return;
+    }
+
+    let Ok(ldtk_project) = ldtk_projects.get_single() else {
+        return;
+    };
+    let Some(ldtk_project) = ldtk_project_assets.get(ldtk_project) else {
+        return;
+    };
+
+    for (level_transform, level_iid) in &level_query {
+        let Some(level) = ldtk_project.get_raw_level_by_iid(&level_iid.to_string()) else {
+            continue;
+        };
+
+        if !level_selection.is_match(&LevelIndices::default(), level) {
+            continue;
+        }
+
+        let Some(grid_size) = level
+            .layer_instances
+            .as_ref()
+            .and_then(|layers| layers.first())
+            .map(|layer| layer.grid_size)
+        else {
+            continue;
+        };
+
+        let tileset = texture_atlases.add(TextureAtlas::from_grid(
+            asset_server.load(TILESET_PATH),
+            Vec2::splat(16.),
+            10,
+            10,
+            None,
+            None,
+        ));
+
+        info!("starting endless tower");
+        tower.layout = Some(TowerLayout {
+            origin: level_transform.translation.truncate() + Vec2::new(0., level.px_hei as f32),
+            width: level.px_wid / grid_size,
+            grid_size,
+            wraps: level_wraps(level),
+            next_row_y: 0,
+            tileset,
+            hazard: asset_server.load(HAZARD_PATH),
+        });
+    }
+}
+
+fn generate_rows(
+    mut commands: Commands,
+    mut tower: ResMut<EndlessTower>,
+    settings: Res<EndlessSettings>,
+    camera_query: Query<(&Transform, &OrthographicProjection)>,
+) {
+    let Ok((camera_transform, projection)) = camera_query.get_single() else {
+        return;
+    };
+    let screen_top = camera_transform.translation.y + projection.area.max.y;
+
+    let EndlessTower { rng, layout, .. } = tower.as_mut();
+    let Some(layout) = layout else {
+        return;
+    };
+
+    while layout.origin.y + ((layout.next_row_y * layout.grid_size) as f32)
+        < screen_top + settings.lookahead
+    {
+        let difficulty =
+            ((layout.next_row_y * layout.grid_size) as f32 / settings.difficulty_height).min(1.);
+        let row = generate_row(rng, &settings, layout, difficulty);
+
+        spawn_row(&mut commands, layout, &row);
+        layout.next_row_y += row.height;
+    }
+}
+
+fn despawn_rows(
+    mut commands: Commands,
+    rows: Query<(Entity, &TowerRow)>,
+    camera_query: Query<(&Transform, &OrthographicProjection)>,
+) {
+    let Ok((camera_transform, projection)) = camera_query.get_single() else {
+        return;
+    };
+    let screen_bottom = camera_transform.translation.y + projection.area.min.y;
+
+    for (entity, row) in &rows {
+        if row.top < screen_bottom {
+            commands.entity(entity).despawn_recursive();
+        }
+    }
+}
+
+#[allow(clippy::type_complexity)]
+fn hazard_contact(
+    mut damage_events: EventWriter<DamageEvent>,
+    players: Query<(Entity, &GlobalTransform), (With<Player>, Without<Dying>)>,
+    hazards: Query<&GlobalTransform, With<Hazard>>,
+) {
+    for (player, player_transform) in &players {
+        let touching = hazards.iter().any(|hazard_transform| {
+            player_transform
+                .translation()
+                .distance(hazard_transform.translation())
+                < HAZARD_DISTANCE
+        });
+
+        if touching {
+            damage_events.send(DamageEvent {
+                target: player,
+                amount: 1,
+                cause: DeathCause::Hazard,
+            });
+        }
+    }
+}
+
+/// Layout of a single row in tile coordinates, relative to the bottom-left of the row.
+#[derive(Debug, PartialEq)]
+struct Row {
+    height: i32,
+    walls: HashSet<GridCoords>,
+    platforms: HashSet<GridCoords>,
+    hazards: Vec<GridCoords>,
+}
+
+fn generate_row(
+    rng: &mut ChaCha8Rng,
+    settings: &EndlessSettings,
+    layout: &TowerLayout,
+    difficulty: f32,
+) -> Row {
+    let width = layout.width;
+    let lerp = |min: f32, max: f32| min + (max - min) * difficulty;
+
+    // the settings can be changed at runtime, so keep every range valid before sampling
+    let (min_row_gap, max_row_gap) =
+        ordered(settings.min_row_gap.max(1), settings.max_row_gap.max(1));
+    let (min_platform_width, max_platform_width) = ordered(
+        settings.min_platform_width.max(1),
+        settings.max_platform_width.max(1),
+    );
+
+    // bias the gap towards the upper end as the tower gets harder
+    let gap = lerp(min_row_gap as f32, max_row_gap as f32) + rng.gen_range(-0.5..0.5);
+    let height = (gap.round() as i32).clamp(min_row_gap, max_row_gap);
+    let hazard_chance =
+        lerp(settings.min_hazard_chance, settings.max_hazard_chance).clamp(0., 1.) as f64;
+
+    let mut walls = HashSet::new();
+    let mut platforms = HashSet::new();
+    let mut hazards = Vec::new();
+
+    // side walls keep the player inside the tower, unless the level wraps around
+    if !layout.wraps {
+        for y in 0..height {
+            walls.insert(GridCoords { x: 0, y });
+            walls.insert(GridCoords { x: width - 1, y });
+        }
+    }
+
+    // the inside of the tower is split in halves so two platforms never overlap
+    let inner = width - 2;
+    let halves = [(1, 1 + inner / 2), (1 + inner / 2, width - 1)];
+    let platform_count = rng.gen_range(1..=2);
+    let mut used_halves = halves.to_vec();
+    used_halves.shuffle(rng);
+
+    for &(start, end) in used_halves.iter().take(platform_count) {
+        // a tower narrower than four tiles has no room in one of its halves
+        if end <= start {
+            continue;
+        }
+
+        let platform_width = rng
+            .gen_range(min_platform_width..=max_platform_width)
+            .min(end - start);
+        let left = rng.gen_range(start..=end - platform_width);
+
+        for x in left..left + platform_width {
+            platforms.insert(GridCoords { x, y: 0 });
+        }
+
+        // only wide platforms get a hazard, and only on an edge, so they can still be landed on
+        if platform_width >= 3 && rng.gen_bool(hazard_chance) {
+            let x = if rng.gen_bool(0.5) {
+                left
+            } else {
+                left + platform_width - 1
+            };
+            hazards.push(GridCoords { x, y: 1 });
+        }
+    }
+
+    Row {
+        height,
+        walls,
+        platforms,
+        hazards,
+    }
+}
+
+fn ordered(a: i32, b: i32) -> (i32, i32) {
+    (a.min(b), a.max(b))
+}
+
+fn spawn_row(commands: &mut Commands, layout: &TowerLayout, row: &Row) {
+    let grid_size = layout.grid_size;
+    let tile_center = |coords: &GridCoords, z: f32| {
+        Transform::from_xyz(
+            (coords.x as f32 + 0.5) * grid_size as f32,
+            (coords.y as f32 + 0.5) * grid_size as f32,
+            z,
+        )
+    };
+    let wall_rects = merge_wall_rects(&row.walls, layout.width, row.height);
+    let platform_rects = merge_wall_rects(&row.platforms, layout.width, row.height);
+    let bottom = layout.origin.y + (layout.next_row_y * grid_size) as f32;
+
+    commands
+        .spawn(SpatialBundle::from_transform(Transform::from_xyz(
+            layout.origin.x,
+            bottom,
+            0.,
+        )))
+        .insert(TowerRow {
+            top: bottom + (row.height * grid_size) as f32,
+        })
+        .with_children(|tower_row| {
+            spawn_wall_rects::<Wall>(tower_row, &wall_rects, grid_size);
+            spawn_wall_rects::<OneWayPlatform>(tower_row, &platform_rects, grid_size);
+
+            if layout.wraps {
+                let platform_seam_rects = seam_rects(&platform_rects, layout.width);
+                spawn_wall_rects::<OneWayPlatform>(tower_row, &platform_seam_rects, grid_size);
+            }
+
+            let tiles = row
+                .walls
+                .iter()
+                .map(|coords| (coords, SIDE_WALL_TILE))
+                .chain(row.platforms.iter().map(|coords| (coords, PLATFORM_TILE)));
+
+            for (coords, index) in tiles {
+                tower_row
+                    .spawn(SpriteSheetBundle {
+                        texture_atlas: layout.tileset.clone(),
+                        sprite: TextureAtlasSprite::new(index),
+                        transform: tile_center(coords, 1.),
+                        ..Default::default()
+                    });
+            }
+
+            for coords in &row.hazards {
+                tower_row
+                    .spawn(SpriteBundle {
+                        texture: layout.hazard.clone(),
+                        transform: tile_center(coords, 2.),
+                        ..Default::default()
+                    })
+                    .insert(Hazard);
+            }
+        });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn layout(width: i32) -> TowerLayout {
+        TowerLayout {
+            origin: Vec2::ZERO,
+            width,
+            grid_size: 16,
+            wraps: false,
+            next_row_y: 0,
+            tileset: Handle::default(),
+            hazard: Handle::default(),
+        }
+    }
+
+    #[test]
+    fn the_same_seed_generates_the_same_rows() {
+        let settings = EndlessSettings::default();
+        let layout = layout(16);
+        let mut first = ChaCha8Rng::seed_from_u64(7);
+        let mut second = ChaCha8Rng::seed_from_u64(7);
+
+        for row in 0..20 {
+            let difficulty = row as f32 / 20.;
+            assert_eq!(
+                generate_row(&mut first, &settings, &layout, difficulty),
+                generate_row(&mut second, &settings, &layout, difficulty)
+            );
+        }
+    }
+
+    #[test]
+    fn survives_inverted_settings_and_narrow_towers() {
+        let settings = EndlessSettings {
+            min_row_gap: 4,
+            max_row_gap: 1,
+            min_platform_width: 5,
+            max_platform_width: 2,
+            min_hazard_chance: -1.,
+            max_hazard_chance: 2.,
+            ..Default::default()
+        };
+        let mut rng = ChaCha8Rng::seed_from_u64(7);
+
+        for width in 0..6 {
+            for _ in 0..20 {
+                let row = generate_row(&mut rng, &settings, &layout(width), 1.);
+                assert!((1..=4).contains(&row.height));
+                for coords in &row.platforms {
+                    assert!((1..width - 1).contains(&coords.x));
+                }
+            }
+        }
+    }
+}
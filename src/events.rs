@@ -10,15 +10,108 @@ pub struct EventPipelinePlugin;
 
 impl Plugin for EventPipelinePlugin {
     fn build(&self, app: &mut App) {
-        app.add_event::<RestartLevelEvent>();
+        app.add_event::<RestartLevelEvent>()
+            .add_event::<PlayerDied>()
+            .add_event::<LevelCompleted>()
+            .add_event::<CoinsChanged>()
+            .add_event::<HeightChanged>()
+            .add_event::<LevelTimeChanged>()
+            .add_event::<ScoreChanged>()
+            .add_event::<EnemyStomped>()
+            .add_event::<DamageEvent>()
+            .add_event::<HealthChanged>()
+            .add_event::<PlayerRespawned>();
     }
 }
 
 
 #[derive(Event, Default)]
-pub struct RestartLevelEvent;
+pub struct RestartLevelEvent {
+    pub scope: RestartScope,
+}
 
+/// How much of the game a `RestartLevelEvent` resets.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum RestartScope {
+    /// Respawn the selected level and start it over from the player's spawn point.
+    #[default]
+    CurrentLevel,
+    /// Reload the whole LDtk world, neighbouring levels included.
+    World,
+    /// Respawn the selected level and put the player back at their last checkpoint.
+    Checkpoint,
+}
 
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum DeathCause {
+    /// Dropped below the bottom of a camera that never scrolls down.
+    FellOffScreen,
+    /// Dropped below the bottom of the level.
+    OutOfBounds,
+    /// Touched a `KillZone` placed in LDtk.
+    KillZone,
+    /// Touched a hazard in the endless tower.
+    Hazard,
+}
 
+#[derive(Event, Clone, Copy, Debug)]
+pub struct PlayerDied {
+    pub cause: DeathCause,
+}
 
+/// Hurts `target`, which dies if this takes the last of their health.
+#[derive(Event, Clone, Copy, Debug)]
+pub struct DamageEvent {
+    pub target: Entity,
+    pub amount: u32,
+    pub cause: DeathCause,
+}
 
+/// Sent whenever the player's health changes, including when a new player spawns.
+#[derive(Event, Clone, Copy, Debug)]
+pub struct HealthChanged {
+    pub current: u32,
+    pub max: u32,
+}
+
+/// Sent when the player reaches a goal.
+#[derive(Event, Default)]
+pub struct LevelCompleted {
+    /// Identifier of the level to go to instead of the next one in world order.
+    pub next_level: Option<String>,
+}
+
+/// Sent once the player is back at their spawn point after dying.
+#[derive(Event, Default)]
+pub struct PlayerRespawned;
+
+/// Sent whenever the number of coins in the player's wallet changes.
+#[derive(Event, Clone, Copy, Debug)]
+pub struct CoinsChanged {
+    pub total: u32,
+}
+
+/// Sent whenever the player climbs or drops a whole meter, heights are in meters
+/// above the level's spawn point.
+#[derive(Event, Clone, Copy, Debug)]
+pub struct HeightChanged {
+    pub height: u32,
+    pub best: u32,
+}
+
+/// Sent once every second of play.
+#[derive(Event, Clone, Copy, Debug)]
+pub struct LevelTimeChanged {
+    pub seconds: u32,
+}
+
+#[derive(Event, Clone, Copy, Debug)]
+pub struct ScoreChanged {
+    pub score: u32,
+}
+
+/// Sent when the player jumps on an enemy.
+// nothing sends it until the game has enemies, the score already pays out for it
+#[allow(dead_code)]
+#[derive(Event, Default)]
+pub struct EnemyStomped;
@@ -0,0 +1,251 @@
+/*
+    Top level game states and the menus between them.
+
+    Gameplay only runs while `Playing`. Pause (Enter / Start) pauses and
+    resumes the game and leaves the title and game over screens, Menus
+    (I / Select) opens the controls screen from the title screen and goes
+    back to the title screen from the pause menu or after a game over.
+    Rapier is frozen in every state but `Playing`.
+*/
+
+use crate::actions::PlatformerAction;
+use crate::player_components::Player;
+use bevy::prelude::*;
+use bevy_rapier2d::prelude::*;
+use leafwing_input_manager::prelude::*;
+
+pub struct GameStatePlugin;
+
+impl Plugin for GameStatePlugin {
+    fn build(&self, app: &mut App) {
+        app.add_state::<GameState>()
+            .add_systems(
+                Update,
+                (
+                    title_input.run_if(in_state(GameState::Title)),
+                    playing_input.run_if(in_state(GameState::Playing)),
+                    paused_input.run_if(in_state(GameState::Paused)),
+                    game_over_input.run_if(in_state(GameState::GameOver)),
+                    freeze_physics.run_if(state_changed::<GameState>()),
+                ),
+            )
+            .add_systems(OnEnter(GameState::Title), show_title)
+            .add_systems(OnEnter(GameState::Paused), (pause_time, show_paused))
+            .add_systems(OnExit(GameState::Paused), resume_time);
+
+        for state in [
+            GameState::Title,
+            GameState::Controls,
+            GameState::Playing,
+            GameState::Paused,
+            GameState::LevelComplete,
+            GameState::GameOver,
+        ] {
+            app.add_systems(OnExit(state), despawn_banners);
+        }
+    }
+}
+
+#[derive(States, Clone, Copy, Debug, Default, Hash, PartialEq, Eq)]
+pub enum GameState {
+    #[default]
+    Title,
+    /// Rebinding the controls, reached from the title screen.
+    Controls,
+    Playing,
+    Paused,
+    /// The short transition between reaching a goal and the next level.
+    LevelComplete,
+    /// The run is over, either by dying in endless mode or finishing the campaign.
+    GameOver,
+}
+
+/// Full screen text shown for the current state, despawned when the state is left.
+#[derive(Component)]
+pub struct Banner;
+
+pub fn spawn_banner(commands: &mut Commands, title: &str, subtitle: &str) {
+    commands
+        .spawn(NodeBundle {
+            style: Style {
+                width: Val::Percent(100.),
+                height: Val::Percent(100.),
+                flex_direction: FlexDirection::Column,
+                justify_content: JustifyContent::Center,
+                align_items: AlignItems::Center,
+                ..Default::default()
+            },
+            ..Default::default()
+        })
+        .insert(Banner)
+        .with_children(|parent| {
+            parent.spawn(TextBundle::from_section(
+                title,
+                TextStyle {
+                    font_size: 48.,
+                    color: Color::WHITE,
+                    ..Default::default()
+                },
+            ));
+            parent.spawn(TextBundle::from_section(
+                subtitle,
+                TextStyle {
+                    font_size: 24.,
+                    color: Color::WHITE,
+                    ..Default::default()
+                },
+            ));
+        });
+}
+
+fn despawn_banners(mut commands: Commands, banners: Query<Entity, With<Banner>>) {
+    for banner in &banners {
+        commands.entity(banner).despawn_recursive();
+    }
+}
+
+fn show_title(mut commands: Commands) {
+    spawn_banner(
+        &mut commands,
+        "Doodle Jump",
+        "Press Enter / Start to play, I / Select for controls",
+    );
+}
+
+fn show_paused(mut commands: Commands) {
+    spawn_banner(
+        &mut commands,
+        "Paused",
+        "Enter / Start to resume, I / Select to quit to the title",
+    );
+}
+
+fn pause_time(mut time: ResMut<Time<Virtual>>) {
+    time.pause();
+}
+
+fn resume_time(mut time: ResMut<Time<Virtual>>) {
+    time.unpause();
+}
+
+fn freeze_physics(state: Res<State<GameState>>, mut rapier_config: ResMut<RapierConfiguration>) {
+    rapier_config.physics_pipeline_active = *state.get() == GameState::Playing;
+}
+
+pub fn just_pressed(
+    players: &Query<&ActionState<PlatformerAction>, With<Player>>,
+    action: PlatformerAction,
+) -> bool {
+    players
+        .iter()
+        .any(|action_state| action_state.just_pressed(action))
+}
+
+fn title_input(
+    players: Query<&ActionState<PlatformerAction>, With<Player>>,
+    mut next_state: ResMut<NextState<GameState>>,
+) {
+    if just_pressed(&players, PlatformerAction::Pause) {
+        next_state.set(GameState::Playing);
+    } else if just_pressed(&players, PlatformerAction::Menus) {
+        next_state.set(GameState::Controls);
+    }
+}
+
+fn playing_input(
+    players: Query<&ActionState<PlatformerAction>, With<Player>>,
+    mut next_state: ResMut<NextState<GameState>>,
+) {
+    if just_pressed(&players, PlatformerAction::Pause) {
+        next_state.set(GameState::Paused);
+    }
+}
+
+fn paused_input(
+    players: Query<&ActionState<PlatformerAction>, With<Player>>,
+    mut next_state: ResMut<NextState<GameState>>,
+) {
+    if just_pressed(&players, PlatformerAction::Pause) {
+        next_state.set(GameState::Playing);
+    } else if just_pressed(&players, PlatformerAction::Menus) {
+        next_state.set(GameState::Title);
+    }
+}
+
+fn game_over_input(
+    players: Query<&ActionState<PlatformerAction>, With<Player>>,
+    mut next_state: ResMut<NextState<GameState>>,
+) {
+    if just_pressed(&players, PlatformerAction::Pause)
+        || just_pressed(&players, PlatformerAction::Menus)
+    {
+        next_state.set(GameState::Title);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn headless_app() -> App {
+        let mut app = App::new();
+        app.add_plugins(MinimalPlugins)
+            .insert_resource(RapierConfiguration::default())
+            .add_plugins(GameStatePlugin);
+        app.world
+            .spawn(Player)
+            .insert(ActionState::<PlatformerAction>::default());
+        app.update();
+        app
+    }
+
+    // a press is seen by the input systems on the first update and the state
+    // changes at the start of the second
+    fn press(app: &mut App, action: PlatformerAction) {
+        let mut players = app
+            .world
+            .query_filtered::<&mut ActionState<PlatformerAction>, With<Player>>();
+        for mut action_state in players.iter_mut(&mut app.world) {
+            action_state.press(action);
+        }
+        app.update();
+        for mut action_state in players.iter_mut(&mut app.world) {
+            action_state.release(action);
+        }
+        app.update();
+    }
+
+    fn state(app: &App) -> GameState {
+        *app.world.resource::<State<GameState>>().get()
+    }
+
+    #[test]
+    fn moves_between_title_playing_paused_and_game_over() {
+        let mut app = headless_app();
+        assert_eq!(state(&app), GameState::Title);
+
+        press(&mut app, PlatformerAction::Pause);
+        assert_eq!(state(&app), GameState::Playing);
+        assert!(app.world.resource::<RapierConfiguration>().physics_pipeline_active);
+
+        press(&mut app, PlatformerAction::Pause);
+        assert_eq!(state(&app), GameState::Paused);
+        assert!(app.world.resource::<Time<Virtual>>().is_paused());
+        assert!(!app.world.resource::<RapierConfiguration>().physics_pipeline_active);
+
+        press(&mut app, PlatformerAction::Pause);
+        assert_eq!(state(&app), GameState::Playing);
+        assert!(!app.world.resource::<Time<Virtual>>().is_paused());
+
+        // what the death sequence does when an endless run ends
+        app.world
+            .resource_mut::<NextState<GameState>>()
+            .set(GameState::GameOver);
+        app.update();
+        assert_eq!(state(&app), GameState::GameOver);
+        assert!(!app.world.resource::<RapierConfiguration>().physics_pipeline_active);
+
+        press(&mut app, PlatformerAction::Menus);
+        assert_eq!(state(&app), GameState::Title);
+    }
+}
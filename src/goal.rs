@@ -3,18 +3,30 @@ use crate::player_components::Player;
 use bevy::prelude::*;
 use bevy_ecs_ldtk::prelude::*;
 use bevy_rapier2d::prelude::*;
-use crate::events::RestartLevelEvent;
+use crate::events::LevelCompleted;
+use crate::game_state::GameState;
 
 pub struct GoalPlugin;
 impl Plugin for GoalPlugin {
     fn build(&self, app: &mut App) {
         app.register_ldtk_entity::<GoalBundle>("Goal")
-            .add_systems(Update, handle_col);
+            .add_systems(Update, handle_col.run_if(in_state(GameState::Playing)));
     }
 }
 
-#[derive(Clone, Default, Copy, Eq, PartialEq, Debug, Component)]
-pub struct Goal;
+#[derive(Clone, Default, Eq, PartialEq, Debug, Component)]
+pub struct Goal {
+    /// Identifier of the level this goal leads to, instead of the next one in world order.
+    pub next_level: Option<String>,
+}
+
+impl From<&EntityInstance> for Goal {
+    fn from(entity_instance: &EntityInstance) -> Self {
+        Goal {
+            next_level: entity_instance.get_string_field("next_level").ok().cloned(),
+        }
+    }
+}
 
 #[derive(Clone, Default, Bundle, LdtkEntity)]
 pub struct GoalBundle {
@@ -22,6 +34,7 @@ pub struct GoalBundle {
     sprite_sheet_bundle: SpriteSheetBundle,
     #[from_entity_instance]
     pub collider_bundle: ColliderBundle,
+    #[from_entity_instance]
     pub goal: Goal,
 }
 
@@ -29,35 +42,22 @@ pub struct GoalBundle {
 // This is the most straightforward way I've found to do a custom collision check.
 pub fn handle_col(
     mut evts: EventReader<CollisionEvent>,
-    mut restart_event: EventWriter<RestartLevelEvent>,
+    mut level_completed: EventWriter<LevelCompleted>,
     player_query: Query<Entity, With<Player>>,
-    goal_query: Query<Entity, With<Goal>>,
+    goal_query: Query<&Goal>,
 ) {
-    
-
-    let player_entity = match player_query.iter().next() {
-        Some(entity) => {entity},
-        None => {
-            println!("No player entity found");
-            return;
-        }
-    };
-
-    let goal_entity = match goal_query.iter().next() {
-        Some(entity) => entity,
-        None => {
-            println!("No goal entity found");
-            return;
-        }
-    };
-
     for evt in evts.read() {
         if let CollisionEvent::Started(e1, e2, _) = evt {
-            //Probably a better way to do this?
-            if (*e1 == player_entity && *e2 == goal_entity) || (*e1 == goal_entity && *e2 == player_entity) {
-                println!("Player and goal intersecting");
-                restart_event.send_default();
-            }
+            let goal = match (goal_query.get(*e1), goal_query.get(*e2)) {
+                (Ok(goal), _) if player_query.contains(*e2) => goal,
+                (_, Ok(goal)) if player_query.contains(*e1) => goal,
+                _ => continue,
+            };
+
+            debug!("player reached a goal");
+            level_completed.send(LevelCompleted {
+                next_level: goal.next_level.clone(),
+            });
         }
     }
 }
@@ -0,0 +1,247 @@
+/*
+    Player health, damage and healing.
+
+    Hazards send a `DamageEvent` instead of killing outright. Each hit costs
+    health and leaves the player invulnerable and blinking for a moment, and
+    the player only dies once their health runs out. Standing still and
+    holding Heal spends coins from the wallet to win health back one point
+    at a time. Health is back to full whenever the player respawns.
+*/
+
+use crate::actions::PlatformerAction;
+use crate::coin::Wallet;
+use crate::death::Dying;
+use crate::events::{DamageEvent, HealthChanged, PlayerDied, PlayerRespawned};
+use crate::game_state::GameState;
+use crate::player::{Event, PlayerState, State};
+use crate::player_components::{float_field, Player};
+use bevy::prelude::*;
+use bevy_ecs_ldtk::prelude::*;
+use leafwing_input_manager::prelude::*;
+
+pub struct HealthPlugin;
+
+impl Plugin for HealthPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(
+            Update,
+            (
+                (take_damage, invulnerability, heal)
+                    .chain()
+                    .run_if(in_state(GameState::Playing)),
+                restore_health,
+                announce_health,
+            )
+                .chain(),
+        );
+    }
+}
+
+/// How many times a second the player blinks while invulnerable.
+const BLINK_FREQUENCY: f32 = 10.;
+const BLINK_ALPHA: f32 = 0.3;
+
+#[derive(Clone, Copy, Debug, Component)]
+pub struct Health {
+    pub current: u32,
+    pub max: u32,
+}
+
+impl Default for Health {
+    fn default() -> Self {
+        Self { current: 3, max: 3 }
+    }
+}
+
+impl From<&EntityInstance> for Health {
+    fn from(entity_instance: &EntityInstance) -> Self {
+        let max = entity_instance
+            .get_int_field("max_health")
+            .map_or(Health::default().max, |&max| max.max(1) as u32);
+
+        Health { current: max, max }
+    }
+}
+
+/// Tunables for getting hurt and healing. Healing turns `heal_cost` coins
+/// into one point of health for every `heal_time` seconds Heal is held, and
+/// each hit is followed by `invulnerability_time` seconds of blinking.
+#[derive(Clone, Copy, Debug, Component)]
+pub struct HealSettings {
+    /// Seconds the player can't be hurt again after taking damage.
+    pub invulnerability_time: f32,
+    /// Seconds Heal has to be held for each point of health.
+    pub heal_time: f32,
+    /// Coins spent on each point of health.
+    pub heal_cost: u32,
+}
+
+impl Default for HealSettings {
+    fn default() -> Self {
+        Self {
+            invulnerability_time: 1.,
+            heal_time: 0.75,
+            heal_cost: 5,
+        }
+    }
+}
+
+impl From<&EntityInstance> for HealSettings {
+    fn from(entity_instance: &EntityInstance) -> Self {
+        let defaults = HealSettings::default();
+
+        HealSettings {
+            invulnerability_time: float_field(
+                entity_instance,
+                "invulnerability_time",
+                defaults.invulnerability_time,
+            ),
+            heal_time: float_field(entity_instance, "heal_time", defaults.heal_time),
+            heal_cost: entity_instance
+                .get_int_field("heal_cost")
+                .map_or(defaults.heal_cost, |&heal_cost| heal_cost.max(0) as u32),
+        }
+    }
+}
+
+/// Present on the player for a moment after taking damage.
+#[derive(Component)]
+pub struct Invulnerable(Timer);
+
+/// Progress towards the next point of health while healing.
+#[derive(Component)]
+pub struct HealTimer(Timer);
+
+#[allow(clippy::type_complexity)]
+fn take_damage(
+    mut commands: Commands,
+    mut damage_events: EventReader<DamageEvent>,
+    mut death_events: EventWriter<PlayerDied>,
+    mut players: Query<
+        (&HealSettings, &mut Health),
+        (With<Player>, Without<Invulnerable>, Without<Dying>),
+    >,
+) {
+    for damage in damage_events.read() {
+        let Ok((settings, mut health)) = players.get_mut(damage.target) else {
+            continue;
+        };
+
+        health.current = health.current.saturating_sub(damage.amount);
+        info!("player took {} damage: {:?}", damage.amount, damage.cause);
+
+        if health.current == 0 {
+            death_events.send(PlayerDied {
+                cause: damage.cause,
+            });
+        } else {
+            commands
+                .entity(damage.target)
+                .insert(Invulnerable(Timer::from_seconds(
+                    settings.invulnerability_time,
+                    TimerMode::Once,
+                )))
+                .remove::<HealTimer>();
+        }
+    }
+}
+
+fn invulnerability(
+    mut commands: Commands,
+    mut players: Query<(Entity, &mut Invulnerable, &mut TextureAtlasSprite), Without<Dying>>,
+    time: Res<Time>,
+) {
+    for (player, mut invulnerable, mut sprite) in &mut players {
+        if invulnerable.0.tick(time.delta()).finished() {
+            sprite.color.set_a(1.);
+            commands.entity(player).remove::<Invulnerable>();
+            continue;
+        }
+
+        let blink = (invulnerable.0.elapsed_secs() * BLINK_FREQUENCY).fract() < 0.5;
+        sprite.color.set_a(if blink { BLINK_ALPHA } else { 1. });
+    }
+}
+
+// Holding Heal while standing still switches to the Healing state, which keeps
+// the player in place until Heal is let go, the health is full or the coins run out.
+#[allow(clippy::type_complexity)]
+fn heal(
+    mut commands: Commands,
+    mut players: Query<
+        (
+            Entity,
+            &ActionState<PlatformerAction>,
+            &HealSettings,
+            &mut Health,
+            &mut Wallet,
+            &mut PlayerState,
+            Option<&mut HealTimer>,
+        ),
+        (With<Player>, Without<Dying>),
+    >,
+    time: Res<Time>,
+) {
+    for (player, action_state, settings, mut health, mut wallet, mut state, heal_timer) in
+        &mut players
+    {
+        let can_heal = health.current < health.max && wallet.coins() >= settings.heal_cost;
+        let holding = action_state.pressed(PlatformerAction::Heal);
+
+        match (state.state(), heal_timer) {
+            (State::Idle {}, _) if holding && can_heal => {
+                state.handle(&Event::Heal);
+                commands
+                    .entity(player)
+                    .insert(HealTimer(Timer::from_seconds(
+                        settings.heal_time,
+                        TimerMode::Repeating,
+                    )));
+            }
+            (State::Healing {}, Some(mut heal_timer)) if holding && can_heal => {
+                if heal_timer.0.tick(time.delta()).just_finished()
+                    && wallet.spend(settings.heal_cost)
+                {
+                    health.current += 1;
+                }
+            }
+            (State::Healing {}, _) => {
+                state.handle(&Event::HealEnd);
+                commands.entity(player).remove::<HealTimer>();
+            }
+            _ => {}
+        }
+    }
+}
+
+fn restore_health(
+    mut commands: Commands,
+    mut respawn_events: EventReader<PlayerRespawned>,
+    mut players: Query<(Entity, &mut Health, &mut TextureAtlasSprite), With<Player>>,
+) {
+    if respawn_events.read().count() == 0 {
+        return;
+    }
+
+    for (player, mut health, mut sprite) in &mut players {
+        health.current = health.max;
+        sprite.color.set_a(1.);
+        commands
+            .entity(player)
+            .remove::<Invulnerable>()
+            .remove::<HealTimer>();
+    }
+}
+
+// Also catches a new player spawning with a level.
+fn announce_health(
+    mut health_changed: EventWriter<HealthChanged>,
+    players: Query<&Health, (With<Player>, Changed<Health>)>,
+) {
+    for health in &players {
+        health_changed.send(HealthChanged {
+            current: health.current,
+            max: health.max,
+        });
+    }
+}
@@ -0,0 +1,230 @@
+/*
+    Heads-up display with the health, coin count, height, level time and score.
+
+    Every label only changes when the event for its value arrives.
+*/
+
+use crate::events::{CoinsChanged, HealthChanged, HeightChanged, LevelTimeChanged, ScoreChanged};
+use crate::game_state::GameState;
+use bevy::prelude::*;
+
+pub struct HudPlugin;
+
+impl Plugin for HudPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(Startup, spawn_hud).add_systems(
+            Update,
+            (
+                show_hud.run_if(state_changed::<GameState>()),
+                update_health,
+                update_coins,
+                update_height,
+                update_level_time,
+                update_score,
+            ),
+        );
+    }
+}
+
+const COIN_ICON_PATH: &str = "2D Pixel Dungeon Asset Pack/items and trap_animation/coin/coin_1.png";
+const FLASK_ICON_PATH: &str =
+    "2D Pixel Dungeon Asset Pack/items and trap_animation/flasks/flasks_1_1.png";
+/// Tint of the flasks standing for health that has been lost.
+const EMPTY_FLASK_COLOR: Color = Color::rgba(0.2, 0.2, 0.2, 0.6);
+const FONT_SIZE: f32 = 24.;
+
+#[derive(Component)]
+pub struct Hud;
+
+/// Row of flasks, one for each point of the player's maximum health.
+#[derive(Component)]
+pub struct HealthBar;
+
+#[derive(Component)]
+pub struct CoinsLabel;
+
+#[derive(Component)]
+pub struct HeightLabel;
+
+#[derive(Component)]
+pub struct LevelTimeLabel;
+
+#[derive(Component)]
+pub struct ScoreLabel;
+
+fn label(text: &str) -> TextBundle {
+    TextBundle::from_section(
+        text,
+        TextStyle {
+            font_size: FONT_SIZE,
+            color: Color::WHITE,
+            ..Default::default()
+        },
+    )
+}
+
+fn spawn_hud(mut commands: Commands, asset_server: Res<AssetServer>) {
+    commands
+        .spawn(NodeBundle {
+            style: Style {
+                width: Val::Percent(100.),
+                padding: UiRect::all(Val::Px(8.)),
+                justify_content: JustifyContent::SpaceBetween,
+                ..Default::default()
+            },
+            visibility: Visibility::Hidden,
+            ..Default::default()
+        })
+        .insert(Hud)
+        .with_children(|hud| {
+            hud.spawn(NodeBundle {
+                style: Style {
+                    flex_direction: FlexDirection::Column,
+                    ..Default::default()
+                },
+                ..Default::default()
+            })
+            .with_children(|left| {
+                left.spawn(NodeBundle {
+                    style: Style {
+                        column_gap: Val::Px(2.),
+                        ..Default::default()
+                    },
+                    ..Default::default()
+                })
+                .insert(HealthBar);
+                left.spawn(NodeBundle {
+                    style: Style {
+                        align_items: AlignItems::Center,
+                        column_gap: Val::Px(4.),
+                        ..Default::default()
+                    },
+                    ..Default::default()
+                })
+                .with_children(|coins| {
+                    coins.spawn(ImageBundle {
+                        image: UiImage::new(asset_server.load(COIN_ICON_PATH)),
+                        style: Style {
+                            width: Val::Px(FONT_SIZE),
+                            height: Val::Px(FONT_SIZE),
+                            ..Default::default()
+                        },
+                        ..Default::default()
+                    });
+                    coins.spawn(label("0")).insert(CoinsLabel);
+                });
+                left.spawn(label("0 m (best 0 m)")).insert(HeightLabel);
+            });
+
+            hud.spawn(NodeBundle {
+                style: Style {
+                    flex_direction: FlexDirection::Column,
+                    align_items: AlignItems::End,
+                    ..Default::default()
+                },
+                ..Default::default()
+            })
+            .with_children(|right| {
+                right.spawn(label("0")).insert(ScoreLabel);
+                right.spawn(label("00:00")).insert(LevelTimeLabel);
+            });
+        });
+}
+
+fn show_hud(state: Res<State<GameState>>, mut huds: Query<&mut Visibility, With<Hud>>) {
+    for mut visibility in &mut huds {
+        *visibility = if *state.get() == GameState::Title {
+            Visibility::Hidden
+        } else {
+            Visibility::Inherited
+        };
+    }
+}
+
+fn update_health(
+    mut commands: Commands,
+    mut health_changed: EventReader<HealthChanged>,
+    health_bars: Query<Entity, With<HealthBar>>,
+    asset_server: Res<AssetServer>,
+) {
+    let Some(health) = health_changed.read().last() else {
+        return;
+    };
+
+    for health_bar in &health_bars {
+        commands
+            .entity(health_bar)
+            .despawn_descendants()
+            .with_children(|flasks| {
+                for point in 0..health.max {
+                    let color = if point < health.current {
+                        Color::WHITE
+                    } else {
+                        EMPTY_FLASK_COLOR
+                    };
+                    flasks.spawn(ImageBundle {
+                        image: UiImage::new(asset_server.load(FLASK_ICON_PATH)),
+                        background_color: color.into(),
+                        style: Style {
+                            width: Val::Px(FONT_SIZE),
+                            height: Val::Px(FONT_SIZE),
+                            ..Default::default()
+                        },
+                        ..Default::default()
+                    });
+                }
+            });
+    }
+}
+
+fn update_coins(
+    mut coins_changed: EventReader<CoinsChanged>,
+    mut labels: Query<&mut Text, With<CoinsLabel>>,
+) {
+    let Some(coins) = coins_changed.read().last() else {
+        return;
+    };
+    for mut text in &mut labels {
+        text.sections[0].value = coins.total.to_string();
+    }
+}
+
+fn update_height(
+    mut height_changed: EventReader<HeightChanged>,
+    mut labels: Query<&mut Text, With<HeightLabel>>,
+) {
+    let Some(height) = height_changed.read().last() else {
+        return;
+    };
+    for mut text in &mut labels {
+        text.sections[0].value = format!("{} m (best {} m)", height.height, height.best);
+    }
+}
+
+fn update_level_time(
+    mut level_time_changed: EventReader<LevelTimeChanged>,
+    mut labels: Query<&mut Text, With<LevelTimeLabel>>,
+) {
+    let Some(level_time) = level_time_changed.read().last() else {
+        return;
+    };
+    for mut text in &mut labels {
+        text.sections[0].value = format!(
+            "{:02}:{:02}",
+            level_time.seconds / 60,
+            level_time.seconds % 60
+        );
+    }
+}
+
+fn update_score(
+    mut score_changed: EventReader<ScoreChanged>,
+    mut labels: Query<&mut Text, With<ScoreLabel>>,
+) {
+    let Some(score) = score_changed.read().last() else {
+        return;
+    };
+    for mut text in &mut labels {
+        text.sections[0].value = score.score.to_string();
+    }
+}
@@ -0,0 +1,304 @@
+/*
+    Local high-score table.
+
+    When a run ends in a game over its score is entered into a top ten of
+    names, scores, seeds and dates, kept in `highscores.ron` in the platform
+    data directory. The file carries a version number so the format can
+    change later without misreading old tables. A table that can't be read is
+    moved aside to `highscores.corrupt` before a new one replaces it, and one
+    from a newer version of the game is left alone without recording scores.
+    Pass `--name <name>` on the command line to choose the name scores are
+    entered under.
+*/
+
+use crate::game_state::GameState;
+use crate::rng::GameRng;
+use crate::score::RunStats;
+use crate::storage;
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+pub struct LeaderboardPlugin {
+    pub name: String,
+}
+
+impl Plugin for LeaderboardPlugin {
+    fn build(&self, app: &mut App) {
+        let mut path = storage::data_path(LEADERBOARD_FILE);
+        if path.is_none() {
+            warn!("no data directory, high scores won't be kept");
+        }
+        let leaderboard = match path.as_deref().map(Leaderboard::load) {
+            None => Leaderboard::default(),
+            Some(Ok(leaderboard)) => leaderboard,
+            Some(Err(error)) => {
+                // keep scores in memory rather than overwrite a table this version can't read
+                warn!("{error}, high scores won't be kept");
+                path = None;
+                Leaderboard::default()
+            }
+        };
+
+        app.insert_resource(PlayerName(self.name.clone()))
+            .insert_resource(LeaderboardPath(path))
+            .insert_resource(leaderboard)
+            .add_systems(OnEnter(GameState::GameOver), record_score);
+    }
+}
+
+const LEADERBOARD_VERSION: u32 = 1;
+const LEADERBOARD_SIZE: usize = 10;
+const LEADERBOARD_FILE: &str = "highscores.ron";
+
+/// Where the table is kept, `None` keeps it in memory only.
+#[derive(Resource, Clone, Debug)]
+pub struct LeaderboardPath(pub Option<PathBuf>);
+
+/// The name new high scores are entered under.
+#[derive(Resource, Clone, Debug)]
+pub struct PlayerName(pub String);
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct HighScore {
+    pub name: String,
+    pub score: u32,
+    /// Seed of the run, to replay it with `--seed`.
+    pub seed: u64,
+    /// When the run ended, in seconds since the Unix epoch.
+    pub date: u64,
+}
+
+/// Just enough of the file to tell which version wrote it.
+#[derive(Deserialize)]
+struct LeaderboardHeader {
+    version: u32,
+}
+
+/// The table as it is stored on disk.
+#[derive(Serialize, Deserialize)]
+struct LeaderboardFile {
+    version: u32,
+    entries: Vec<HighScore>,
+}
+
+/// The best scores so far, highest first.
+#[derive(Resource, Default, Debug)]
+pub struct Leaderboard {
+    pub entries: Vec<HighScore>,
+}
+
+impl Leaderboard {
+    /// Enters a score into the table and returns its rank, or `None` if it
+    /// didn't make the top ten. Ties go below the scores already there.
+    pub fn insert(&mut self, high_score: HighScore) -> Option<usize> {
+        let rank = self
+            .entries
+            .iter()
+            .position(|entry| entry.score < high_score.score)
+            .unwrap_or(self.entries.len());
+        if rank >= LEADERBOARD_SIZE {
+            return None;
+        }
+
+        self.entries.insert(rank, high_score);
+        self.entries.truncate(LEADERBOARD_SIZE);
+        Some(rank)
+    }
+
+    /// Reads the table at `path`, starting an empty one if there is none or
+    /// it can't be read. Only a table from a newer version is an error.
+    pub fn load(path: &Path) -> Result<Self, String> {
+        let Ok(contents) = std::fs::read_to_string(path) else {
+            return Ok(Self::default());
+        };
+
+        let error = match ron::from_str::<LeaderboardHeader>(&contents) {
+            Ok(LeaderboardHeader {
+                version: LEADERBOARD_VERSION,
+            }) => match ron::from_str::<LeaderboardFile>(&contents) {
+                Ok(file) => {
+                    return Ok(Self {
+                        entries: file.entries,
+                    })
+                }
+                Err(error) => error.to_string(),
+            },
+            Ok(LeaderboardHeader { version }) if version > LEADERBOARD_VERSION => {
+                return Err(format!(
+                    "{} has version {version}, this game only reads {LEADERBOARD_VERSION}",
+                    path.display()
+                ));
+            }
+            Ok(LeaderboardHeader { version }) => format!("unknown version {version}"),
+            Err(error) => error.to_string(),
+        };
+
+        let backup = path.with_extension("corrupt");
+        warn!(
+            "couldn't read {}: {error}, moving it to {} and starting a new high-score table",
+            path.display(),
+            backup.display()
+        );
+        if let Err(error) = std::fs::rename(path, &backup) {
+            warn!("couldn't move {}: {error}", path.display());
+        }
+        Ok(Self::default())
+    }
+
+    pub fn save(&self, path: &Path) -> Result<(), String> {
+        let file = LeaderboardFile {
+            version: LEADERBOARD_VERSION,
+            entries: self.entries.clone(),
+        };
+        let contents = ron::ser::to_string_pretty(&file, ron::ser::PrettyConfig::default())
+            .map_err(|error| error.to_string())?;
+        storage::write_atomic(path, &contents).map_err(|error| error.to_string())
+    }
+}
+
+fn record_score(
+    mut leaderboard: ResMut<Leaderboard>,
+    stats: Res<RunStats>,
+    leaderboard_path: Res<LeaderboardPath>,
+    player_name: Res<PlayerName>,
+    game_rng: Res<GameRng>,
+) {
+    let date = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |elapsed| elapsed.as_secs());
+
+    let Some(rank) = leaderboard.insert(HighScore {
+        name: player_name.0.clone(),
+        score: stats.score(),
+        seed: game_rng.seed(),
+        date,
+    }) else {
+        return;
+    };
+
+    info!("new high score: {} at rank {}", stats.score(), rank + 1);
+    let Some(path) = &leaderboard_path.0 else {
+        return;
+    };
+    if let Err(error) = leaderboard.save(path) {
+        warn!("couldn't save high scores: {error}");
+    }
+}
+
+/// Reads `--name <name>` from the command line, falling back to the user's login name.
+pub fn name_from_args() -> String {
+    std::env::args()
+        .skip_while(|arg| arg != "--name")
+        .nth(1)
+        .or_else(|| std::env::var("USER").ok())
+        .or_else(|| std::env::var("USERNAME").ok())
+        .unwrap_or_else(|| "Player".to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn high_score(name: &str, score: u32) -> HighScore {
+        HighScore {
+            name: name.to_string(),
+            score,
+            seed: 0,
+            date: 0,
+        }
+    }
+
+    fn names(leaderboard: &Leaderboard) -> Vec<&str> {
+        leaderboard
+            .entries
+            .iter()
+            .map(|entry| entry.name.as_str())
+            .collect()
+    }
+
+    fn temporary_path(name: &str) -> PathBuf {
+        std::env::temp_dir().join(format!(
+            "{}-{}-highscores-{name}.ron",
+            env!("CARGO_PKG_NAME"),
+            std::process::id()
+        ))
+    }
+
+    #[test]
+    fn ranks_scores_highest_first() {
+        let mut leaderboard = Leaderboard::default();
+
+        assert_eq!(leaderboard.insert(high_score("middle", 20)), Some(0));
+        assert_eq!(leaderboard.insert(high_score("top", 30)), Some(0));
+        assert_eq!(leaderboard.insert(high_score("bottom", 10)), Some(2));
+        assert_eq!(names(&leaderboard), ["top", "middle", "bottom"]);
+    }
+
+    #[test]
+    fn ties_go_below_the_scores_already_there() {
+        let mut leaderboard = Leaderboard::default();
+        leaderboard.insert(high_score("first", 20));
+        leaderboard.insert(high_score("lower", 10));
+
+        assert_eq!(leaderboard.insert(high_score("second", 20)), Some(1));
+        assert_eq!(names(&leaderboard), ["first", "second", "lower"]);
+    }
+
+    #[test]
+    fn keeps_only_the_top_ten() {
+        let mut leaderboard = Leaderboard::default();
+        for score in 1..=LEADERBOARD_SIZE as u32 {
+            leaderboard.insert(high_score("filler", score * 10));
+        }
+
+        assert_eq!(leaderboard.insert(high_score("too low", 5)), None);
+        assert_eq!(leaderboard.insert(high_score("tied last", 10)), None);
+        assert_eq!(leaderboard.entries.len(), LEADERBOARD_SIZE);
+
+        assert_eq!(leaderboard.insert(high_score("new best", 1000)), Some(0));
+        assert_eq!(leaderboard.entries.len(), LEADERBOARD_SIZE);
+        assert_eq!(leaderboard.entries.last().unwrap().score, 20);
+    }
+
+    #[test]
+    fn round_trips_through_the_file() {
+        let path = temporary_path("round-trip");
+        let mut leaderboard = Leaderboard::default();
+        leaderboard.insert(high_score("someone", 42));
+        leaderboard.save(&path).unwrap();
+
+        let reloaded = Leaderboard::load(&path).unwrap();
+        assert_eq!(reloaded.entries, leaderboard.entries);
+
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn moves_an_unreadable_table_aside() {
+        let path = temporary_path("corrupt");
+        let backup = path.with_extension("corrupt");
+        std::fs::write(&path, "(version: 1, entries: \"lots\")").unwrap();
+
+        let leaderboard = Leaderboard::load(&path).unwrap();
+        assert!(leaderboard.entries.is_empty());
+        assert!(!path.exists());
+        assert!(backup.exists());
+
+        std::fs::remove_file(&backup).unwrap();
+    }
+
+    #[test]
+    fn leaves_tables_from_newer_versions_alone() {
+        let path = temporary_path("newer");
+        let contents = format!("(version: {}, scores: {{}})", LEADERBOARD_VERSION + 1);
+        std::fs::write(&path, &contents).unwrap();
+
+        assert!(Leaderboard::load(&path).is_err());
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), contents);
+
+        std::fs::remove_file(&path).unwrap();
+    }
+}
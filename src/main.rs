@@ -14,21 +14,49 @@ mod respawn;
 mod state_machine;
 mod actions;
 mod player_components;
+mod endless;
+mod rng;
+mod wrap;
+mod one_way;
+mod spring;
+mod moving_platform;
+mod crumbling_platform;
+mod camera;
+mod death;
+mod checkpoint;
+mod campaign;
+mod game_state;
+mod score;
+mod hud;
+mod leaderboard;
+mod storage;
+mod save;
+mod controls;
+mod dash;
+mod health;
+mod crouch;
+mod animation;
 
 
 fn main() {
-    App::new()
+    let seed = rng::seed_from_args().unwrap_or_else(rand::random);
+    let name = leaderboard::name_from_args();
+
+    let mut app = App::new();
+    app
         .add_plugins(DefaultPlugins.set(ImagePlugin::default_nearest()))
+        .add_plugins(rng::RngPlugin { seed })
         .add_plugins(LdtkPlugin)
         .add_plugins(InputManagerPlugin::<PlatformerAction>::default())
         .insert_resource(RapierConfiguration {
             gravity: Vec2::new(0.0, -2000.0),
             ..Default::default()
         })
-        .insert_resource(LevelSelection::Uid(0))
+        .insert_resource(LevelSelection::index(0))
         .insert_resource(LdtkSettings {
+            // every level has its own player, so only the one being played may spawn
             level_spawn_behavior: LevelSpawnBehavior::UseWorldTranslation {
-                load_level_neighbors: true,
+                load_level_neighbors: false,
             },
             set_clear_color: SetClearColor::FromLevelBackground,
             ..Default::default()
@@ -36,19 +64,53 @@ fn main() {
         .add_plugins(RapierPhysicsPlugin::<NoUserData>::pixels_per_meter(100.0))
         .add_plugins(RapierDebugRenderPlugin::default())
         .add_plugins(events::EventPipelinePlugin)
-        .add_systems(Startup, systems::setup_camera)
+        .add_plugins(game_state::GameStatePlugin)
         .add_systems(Startup, systems::setup_ldtk_world)
         .add_systems(Update, display_events)
         .add_systems(Update, systems::ground_detection)
         .add_systems(Update, systems::update_on_ground)
-        .add_systems(Update, systems::spawn_wall_collision)
+        .add_systems(Update, systems::wall_detection)
+        .add_systems(Update, systems::update_on_wall)
+        .add_systems(Update, systems::spawn_wall_collision::<components::Wall>)
+        .add_systems(Update, systems::spawn_wall_collision::<components::OneWayPlatform>)
         .add_systems(Update, systems::spawn_ground_sensor)
+        .add_systems(Update, systems::spawn_wall_sensors)
         .register_ldtk_int_cell::<components::WallBundle>(1)
+        .register_ldtk_int_cell::<components::OneWayPlatformBundle>(2)
         .add_plugins(coin::CoinPlugin)
         .add_plugins(player::PlayerPlugin)
         .add_plugins(goal::GoalPlugin)
         .add_plugins(respawn::RespawnPlugin)
-        .run();
+        .add_plugins(endless::EndlessPlugin)
+        .add_plugins(wrap::WrapPlugin)
+        .add_plugins(one_way::OneWayPlugin)
+        .add_plugins(bevy_easings::EasingsPlugin)
+        .add_plugins(spring::SpringPlugin)
+        .add_plugins(moving_platform::MovingPlatformPlugin)
+        .add_plugins(crumbling_platform::CrumblingPlatformPlugin)
+        .add_plugins(camera::CameraPlugin)
+        .add_plugins(death::DeathPlugin)
+        .add_plugins(checkpoint::CheckpointPlugin)
+        .add_plugins(campaign::CampaignPlugin)
+        .add_plugins(score::ScorePlugin)
+        .add_plugins(hud::HudPlugin)
+        .add_plugins(leaderboard::LeaderboardPlugin { name })
+        .add_plugins(save::SavePlugin::default())
+        .add_plugins(controls::ControlsPlugin)
+        .add_plugins(dash::DashPlugin)
+        .add_plugins(health::HealthPlugin)
+        .add_plugins(crouch::CrouchPlugin)
+        .add_plugins(animation::AnimationPlugin);
+
+    if std::env::args().any(|arg| arg == "--endless") {
+        app.init_resource::<endless::EndlessTower>()
+            .insert_resource(camera::CameraSettings {
+                never_scroll_down: true,
+                ..Default::default()
+            });
+    }
+
+    app.run();
 }
 
 
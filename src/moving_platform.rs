@@ -0,0 +1,186 @@
+/*
+    Kinematic platforms that follow a path set up in LDtk.
+
+    The `MovingPlatform` entity takes a `path` Point array, a `speed` in pixels
+    per second and a `mode` enum (`Loop` or `PingPong`). The platform starts at
+    the position it was placed at and visits the path points in order. Anyone
+    standing on it is carried along by adding the platform's displacement to
+    their character controller.
+*/
+
+use crate::components::ColliderBundle;
+use crate::game_state::GameState;
+use crate::player::change_character_position;
+use crate::player_components::float_field;
+use bevy::prelude::*;
+use bevy_ecs_ldtk::prelude::*;
+
+pub struct MovingPlatformPlugin;
+
+impl Plugin for MovingPlatformPlugin {
+    fn build(&self, app: &mut App) {
+        app.register_ldtk_entity::<MovingPlatformBundle>("MovingPlatform")
+            .add_systems(
+                Update,
+                (
+                    place_waypoints,
+                    move_platforms.run_if(in_state(GameState::Playing)),
+                )
+                    .chain()
+                    .before(change_character_position),
+            );
+    }
+}
+
+/// Default `speed` when the LDtk field is missing, in pixels per second.
+const DEFAULT_SPEED: f32 = 32.;
+
+/// What a platform does after reaching the last point of its path.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum PathMode {
+    /// Head straight back to the first point and go around again.
+    Loop,
+    /// Retrace the path backwards.
+    #[default]
+    PingPong,
+}
+
+#[derive(Clone, Debug, Component)]
+pub struct MovingPlatform {
+    pub speed: f32,
+    pub mode: PathMode,
+    /// The path in grid cells relative to where the platform was placed, y up.
+    path: Vec<IVec2>,
+    /// The path as translations, known once LDtk has placed the platform.
+    waypoints: Vec<Vec2>,
+    target: usize,
+    reversing: bool,
+    /// How far the platform moved this frame.
+    pub displacement: Vec2,
+}
+
+impl Default for MovingPlatform {
+    fn default() -> Self {
+        MovingPlatform {
+            speed: DEFAULT_SPEED,
+            mode: PathMode::default(),
+            path: vec![IVec2::ZERO],
+            waypoints: Vec::new(),
+            target: 0,
+            reversing: false,
+            displacement: Vec2::ZERO,
+        }
+    }
+}
+
+impl From<&EntityInstance> for MovingPlatform {
+    fn from(entity_instance: &EntityInstance) -> Self {
+        let mode = match entity_instance.get_enum_field("mode").map(String::as_str) {
+            Ok("Loop") => PathMode::Loop,
+            _ => PathMode::PingPong,
+        };
+
+        // LDtk grid coordinates point down, so flip them while making them relative
+        let start = entity_instance.grid;
+        let path = std::iter::once(IVec2::ZERO)
+            .chain(
+                entity_instance
+                    .iter_points_field("path")
+                    .into_iter()
+                    .flatten()
+                    .map(|point| IVec2::new(point.x - start.x, start.y - point.y)),
+            )
+            .collect();
+
+        MovingPlatform {
+            speed: float_field(entity_instance, "speed", DEFAULT_SPEED),
+            mode,
+            path,
+            ..Default::default()
+        }
+    }
+}
+
+impl MovingPlatform {
+    fn advance(&mut self) {
+        let last = self.waypoints.len() - 1;
+
+        self.target = match self.mode {
+            PathMode::Loop => (self.target + 1) % self.waypoints.len(),
+            PathMode::PingPong => {
+                if self.target == last {
+                    self.reversing = true;
+                } else if self.target == 0 {
+                    self.reversing = false;
+                }
+
+                if self.reversing {
+                    self.target - 1
+                } else {
+                    self.target + 1
+                }
+            }
+        };
+    }
+}
+
+#[derive(Clone, Default, Bundle, LdtkEntity)]
+pub struct MovingPlatformBundle {
+    #[sprite_sheet_bundle]
+    sprite_sheet_bundle: SpriteSheetBundle,
+    #[from_entity_instance]
+    pub collider_bundle: ColliderBundle,
+    #[from_entity_instance]
+    pub moving_platform: MovingPlatform,
+}
+
+// The path is stored in grid cells until LDtk has positioned the platform inside
+// its layer, which is also where the grid size comes from.
+fn place_waypoints(
+    mut platforms: Query<(&mut MovingPlatform, &Transform, &Parent), Added<MovingPlatform>>,
+    layers: Query<&LayerMetadata>,
+) {
+    for (mut platform, transform, parent) in &mut platforms {
+        let grid_size = layers
+            .get(parent.get())
+            .map_or(16, |layer| layer.grid_size) as f32;
+        let start = transform.translation.truncate();
+
+        platform.waypoints = platform
+            .path
+            .iter()
+            .map(|cell| start + cell.as_vec2() * grid_size)
+            .collect();
+        platform.target = 0;
+    }
+}
+
+fn move_platforms(mut platforms: Query<(&mut MovingPlatform, &mut Transform)>, time: Res<Time>) {
+    for (mut platform, mut transform) in &mut platforms {
+        let start = transform.translation.truncate();
+        let mut position = start;
+        let mut remaining = platform.speed * time.delta_seconds();
+
+        // a fast platform can pass several points in one frame, but never loops the
+        // whole path more than once, which also stops a path of identical points
+        for _ in 0..platform.waypoints.len() {
+            if platform.waypoints.len() < 2 || remaining <= 0. {
+                break;
+            }
+
+            let target = platform.waypoints[platform.target];
+            let distance = position.distance(target);
+            if distance > remaining {
+                position += (target - position) / distance * remaining;
+                break;
+            }
+
+            position = target;
+            remaining -= distance;
+            platform.advance();
+        }
+
+        platform.displacement = position - start;
+        transform.translation = position.extend(transform.translation.z);
+    }
+}
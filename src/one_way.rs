@@ -0,0 +1,113 @@
+use crate::actions::PlatformerAction;
+use crate::components::{GroundSensor, OneWayPlatform};
+use crate::game_state::GameState;
+use crate::player_components::{JumpState, Player};
+use bevy::prelude::*;
+use bevy_rapier2d::prelude::*;
+use leafwing_input_manager::plugin::InputManagerSystem;
+use leafwing_input_manager::prelude::*;
+
+/// How long one-way platforms stay disabled after dropping through one.
+const DROP_THROUGH_TIME: f32 = 0.25;
+/// How far below the top of a platform the player's feet can be and still land on it.
+const LANDING_TOLERANCE: f32 = 1.0;
+
+pub struct OneWayPlugin;
+
+impl Plugin for OneWayPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(
+            PreUpdate,
+            drop_through
+                .after(InputManagerSystem::Update)
+                .run_if(in_state(GameState::Playing)),
+        )
+        .add_systems(
+            Update,
+            (
+                tick_drop_through.run_if(in_state(GameState::Playing)),
+                toggle_one_way_colliders,
+            )
+                .chain(),
+        );
+    }
+}
+
+/// Present on a player while they are dropping through a one-way platform.
+#[derive(Component)]
+pub struct DropThrough(Timer);
+
+// Down + Jump while standing on a one-way platform drops through it instead of jumping.
+#[allow(clippy::type_complexity)]
+fn drop_through(
+    mut commands: Commands,
+    players: Query<(Entity, &ActionState<PlatformerAction>), (With<Player>, Without<DropThrough>)>,
+    ground_sensors: Query<&GroundSensor>,
+    one_way_platforms: Query<(), (With<OneWayPlatform>, With<Collider>)>,
+) {
+    for (player, action_state) in &players {
+        if !(action_state.pressed(PlatformerAction::Down)
+            && action_state.just_pressed(PlatformerAction::Jump))
+        {
+            continue;
+        }
+
+        let on_one_way_platform = ground_sensors
+            .iter()
+            .filter(|sensor| sensor.ground_detection_entity == player)
+            .flat_map(|sensor| &sensor.intersecting_ground_entities)
+            .any(|&ground| one_way_platforms.contains(ground));
+
+        if on_one_way_platform {
+            commands
+                .entity(player)
+                .insert(DropThrough(Timer::from_seconds(DROP_THROUGH_TIME, TimerMode::Once)));
+        }
+    }
+}
+
+fn tick_drop_through(
+    mut commands: Commands,
+    mut players: Query<(Entity, &mut DropThrough)>,
+    time: Res<Time>,
+) {
+    for (player, mut drop_through) in &mut players {
+        if drop_through.0.tick(time.delta()).finished() {
+            commands.entity(player).remove::<DropThrough>();
+        }
+    }
+}
+
+// A one-way platform only has a collider while the player is falling onto it from above.
+#[allow(clippy::type_complexity)]
+fn toggle_one_way_colliders(
+    mut commands: Commands,
+    players: Query<(&GlobalTransform, &Collider, &JumpState, Has<DropThrough>), With<Player>>,
+    platforms: Query<
+        (Entity, &GlobalTransform, &Collider, Has<ColliderDisabled>),
+        With<OneWayPlatform>,
+    >,
+) {
+    let Ok((player_transform, player_collider, jump, dropping)) = players.get_single() else {
+        return;
+    };
+    let feet = player_transform.translation().y
+        - player_collider
+            .as_cuboid()
+            .map_or(0., |cuboid| cuboid.half_extents().y);
+
+    for (platform, platform_transform, platform_collider, disabled) in &platforms {
+        let top = platform_transform.translation().y
+            + platform_collider
+                .as_cuboid()
+                .map_or(0., |cuboid| cuboid.half_extents().y);
+
+        let solid = !dropping && jump.velocity <= 0. && feet >= top - LANDING_TOLERANCE;
+
+        if solid && disabled {
+            commands.entity(platform).remove::<ColliderDisabled>();
+        } else if !solid && !disabled {
+            commands.entity(platform).insert(ColliderDisabled);
+        }
+    }
+}
@@ -8,12 +8,14 @@ use statig::{
 
 };
 use crate::player_components::*;
+use crate::components::{GroundSensor, WallDetection};
+use crate::death::Dying;
+use crate::game_state::GameState;
+use crate::moving_platform::MovingPlatform;
+use crate::one_way::DropThrough;
 use std::time::Duration;
 
 
-
-const MOVEMENT_SPEED: f32 = 1.;
-
 /// Plugin for spawning the player and controlling them.
 pub struct PlayerPlugin;
 
@@ -22,268 +24,336 @@ impl Plugin for PlayerPlugin {
         app.add_systems(
             Update,
             (
-                camera_fit_inside_current_level,
-                change_character_position,
-                fall,
-                jump,
                 machine_events,
-            ),
+                jump,
+                fall,
+                change_character_position,
+            )
+                .chain()
+                .run_if(in_state(GameState::Playing)),
         )
         .register_ldtk_entity::<PlayerBundle>("Player");
     }
 }
 
 
+#[allow(clippy::type_complexity)]
 fn machine_events(
-    query_action_state: Query<
-        &ActionState<PlatformerAction>,
-    >,
-    mut commands: Commands,
     mut controllers: Query<(
         &KinematicCharacterControllerOutput,
+        &ActionState<PlatformerAction>,
+        &JumpSettings,
+        &mut JumpState,
         &mut PlayerState,
+        &WallDetection,
+        &WallSettings,
+        &mut MovementState,
+        Has<DropThrough>,
     )>,
     time: Res<Time>,
 ) {
-    for (_output, mut state_machine) in
-        &mut controllers
+    let event_time = time.elapsed();
+
+    for (
+        output,
+        action_state,
+        settings,
+        mut jump,
+        mut state_machine,
+        walls,
+        wall_settings,
+        mut movement,
+        dropping,
+    ) in &mut controllers
     {
+        let last_jump = state_machine.0.last_jump;
+        // falling against a wall while pushing into it, or not pushing away from it
+        let input = horizontal_input(action_state);
+        let pushing_into_wall = walls.side().is_some_and(|side| input * side > 0.);
+        let against_wall = walls.side().is_some_and(|side| input * side >= 0.);
+
         match state_machine.0.state() {
-            // State::Idle {  } => todo!(),
+            State::Idle {} => {
+                if !output.grounded {
+                    state_machine.0.handle(&Event::Fall { event_time });
+                }
+            }
             State::Jumping {} => {
-                if let Some(last_jump) =
-                    state_machine.0.last_jump
-                {
-                    if (time.elapsed() - last_jump)
-                        > Duration::from_millis(500)
-                    {
-                        state_machine
-                            .0
-                            .handle(&Event::Fall);
-                    }
+                if jump.peak_reached {
+                    state_machine.0.handle(&Event::Jump {
+                        event_time,
+                        rising: false,
+                        peak_reached: true,
+                    });
                 }
             }
-            // State::Crouching {  } => todo!(),
-            // State::Falling {  } => todo!(),
-            // State::Healing {  } => todo!(),
-            _ => {}
-        }
-    }
-    for action_state in &query_action_state {
-        for (output, mut state_machine) in
-            &mut controllers
-        {
-            if time.elapsed().as_millis() % 1000 == 0 {
-                println!("state: {:?}", state_machine.0.state());
+            State::Falling {} => {
+                if output.grounded {
+                    state_machine.0.handle(&Event::Land { event_time });
+                } else if pushing_into_wall {
+                    state_machine.0.handle(&Event::WallSlide);
+                }
             }
-            match state_machine.0.state() {
-                State::Idle {} => {
-                    if action_state.just_pressed(
-                        PlatformerAction::Jump,
-                    ) {
-                        println!("Jumping");
-                        state_machine.0.handle(
-                            &Event::Jump {
-                                event_time: time.elapsed(),
-                                rising: true,
-                                peak_reached: false,
-                            },
-                        );
-                    }
+            State::WallSliding {} => {
+                if output.grounded {
+                    state_machine.0.handle(&Event::Land { event_time });
+                } else if !against_wall {
+                    state_machine.0.handle(&Event::Fall { event_time });
                 }
-                State::Jumping {} => {
-                     info!("jumping");
-                    if let Some(last_jump) =
-                        state_machine.0.last_jump
-                    {
-                        if output.grounded
-                            && 
-                            // systems can run fast enough that the newly jumping
-                            // player can still be in their original pre-takeoff contact
-                            // with the ground
-                            //
-                            // maybe replace with "last_left_ground" field?
-                            time.elapsed() - last_jump
-                                > Duration::from_millis(50)
-                        {
-                            state_machine
-                                .0
-                                .handle(&Event::Land);
-                        }
-                    }
+            }
+            State::Healing {} | State::Crouching {} => {
+                if !output.grounded {
+                    state_machine.0.handle(&Event::Fall { event_time });
                 }
-                State::Falling {} => {
+            }
+            // the dash system ends the dash
+            State::Dashing {} => {}
+        }
 
-                }
+        let wall_side = walls
+            .side()
+            .filter(|_| matches!(state_machine.0.state(), State::WallSliding {}));
+
+        // Presses are always forwarded, the machine decides whether they start a
+        // jump right away, use coyote time, or get buffered until landing.
+        // Down + Jump drops through one-way platforms instead.
+        if action_state.just_pressed(PlatformerAction::Jump) && !dropping {
+            state_machine.0.handle(&Event::Jump {
+                event_time,
+                rising: true,
+                peak_reached: false,
+            });
+        }
+
+        if state_machine.0.last_jump != last_jump {
+            jump.time.reset();
+            jump.velocity = settings.jump_velocity();
+            jump.rising = true;
+            jump.peak_reached = false;
+
+            // a wall jump kicks away from the wall, too fast to steer back at first
+            if let Some(side) = wall_side {
+                movement.velocity = -side * wall_settings.wall_jump_speed;
+                movement.facing = -side;
+                movement.input_locked_until =
+                    event_time + Duration::from_secs_f32(wall_settings.wall_jump_lockout);
             }
+        } else if let State::Idle {} = state_machine.0.state() {
+            jump.velocity = 0.;
         }
     }
 }
 
+// Integrates gravity on the falling half of the arc, sliding down a wall is
+// capped at a much lower speed.
 fn fall(
-    mut controllers: Query<&mut KinematicCharacterController, With<Player>>,
-    mut controller_outputs: Query<(Entity, &KinematicCharacterControllerOutput)>,
-    mut state_machine: Query<&mut PlayerState>,
+    mut controllers: Query<(&JumpSettings, &WallSettings, &mut JumpState, &PlayerState)>,
+    time: Res<Time>,
 ) {
-    for (_, output) in &mut controller_outputs {
-        println!("output: {:?}", output.grounded);
+    for (settings, wall_settings, mut jump, state_machine) in &mut controllers {
+        let max_fall_speed = match state_machine.0.state() {
+            State::Falling {} => settings.max_fall_speed,
+            State::WallSliding {} => wall_settings.wall_slide_speed,
+            _ => continue,
+        };
+
+        let gravity = settings.gravity() * settings.fall_gravity_multiplier;
+        jump.velocity = (jump.velocity - gravity * time.delta_seconds()).max(-max_fall_speed);
     }
-
-    for mut state in state_machine.iter_mut() {
-        match state.0.state() {
-            State::Falling {} => {
-                    for mut controller in controllers.iter_mut() {
-                        println!("controller: {:?}", controller.translation);
-                        controller.translation = match controller.translation {
-                            Some(mut v) => {
-                                v.y = -10.0;
-                                Some(v)
-                            }
-                            None => Some(Vec2::new(0.0, -10.0)),
-                        };
-                    }
-                for (_, output) in controller_outputs.iter() {
-                    println!("output: {:?}", output.grounded);
-                    if output.grounded {
-                        state.0.handle(&Event::Land);
-                    } else {
-
-                    }
-                }
-            }
-            _ => {}
-        }
-    }    
 }
 
+// Integrates gravity on the rising half of the arc, cutting the jump short when
+// Jump is released early and flagging the apex once the upward velocity is spent.
 fn jump(
-    mut commands: Commands,
     mut controllers: Query<(
-        &mut KinematicCharacterController,
-        // &KinematicCharacterControllerOutput,
-        &Velocity,
-        &mut PlayerState,
         &ActionState<PlatformerAction>,
+        &JumpSettings,
+        &mut JumpState,
+        &PlayerState,
     )>,
     time: Res<Time>,
 ) {
-    for (
-        mut controller,
-        // output,
-        velocity,
-        mut state_machine,
-        action_state,
-    ) in &mut controllers
-    { 
-        if action_state
-            .just_released(PlatformerAction::Jump)
-        {
-            println!("transitioning to falling");
-            state_machine.0.handle(&Event::Fall);
-        } else if let State::Jumping {} =
-            state_machine.0.state()
-        {
-            println!("jumping handler");
-            controller.translation =
-                match controller.translation {
-                    Some(mut v) => {
-                        v.y = 10.0;
-                        Some(v)
-                    }
-                    None => Some(Vec2::new(0.0, 10.0)),
-                };
+    for (action_state, settings, mut jump, state_machine) in &mut controllers {
+        if let State::Jumping {} = state_machine.0.state() {
+            jump.time.tick(time.delta());
+
+            if jump.rising && action_state.just_released(PlatformerAction::Jump) {
+                jump.velocity *= settings.jump_cut_multiplier;
+            }
+
+            jump.velocity -= settings.gravity() * time.delta_seconds();
+
+            if jump.velocity <= 0. {
+                jump.velocity = 0.;
+                jump.rising = false;
+                jump.peak_reached = true;
+            }
         }
     }
 }
 
 
-//Responsible for moving character
-fn change_character_position(
-    input: Res<Input<KeyCode>>,
-    query_action_state: Query<&ActionState<PlatformerAction>>,
-    mut character_controllers: Query<&mut KinematicCharacterController, With<Player>>,
-    mut state: Query<&mut PlayerState>,
-    player_query: Query<&mut Transform,  With<Player>>,
-    mut jump_duration: Query<&mut JumpState, With<Player>>,
-    time: Res<Time>, 
+//Responsible for moving character, the player stays frozen in place while dying
+#[allow(clippy::type_complexity)]
+pub fn change_character_position(
+    mut character_controllers: Query<
+        (
+            Entity,
+            &mut KinematicCharacterController,
+            &ActionState<PlatformerAction>,
+            &MovementSettings,
+            &mut MovementState,
+            &JumpState,
+            &PlayerState,
+        ),
+        (With<Player>, Without<Dying>),
+    >,
+    ground_sensors: Query<&GroundSensor>,
+    moving_platforms: Query<&MovingPlatform>,
+    time: Res<Time>,
 ) {
-    if let Ok(Transform {
-        ..
-    }) = player_query.get_single()
+    for (
+        player,
+        mut character_controller,
+        action_state,
+        settings,
+        mut movement,
+        jump,
+        state_machine,
+    ) in character_controllers.iter_mut()
     {
-        
-        let mut jump = jump_duration.single_mut();
-
-        for query in query_action_state.iter() {
-            if query.just_pressed(PlatformerAction::Jump) {
-            }
-            if query.just_released(PlatformerAction::Left) {
-                info!("Left");
-            }
-            if query.just_released(PlatformerAction::Move) {
-                info!("Move");
-            }
-            if query.just_released(PlatformerAction::Right) {
-                info!("Right");
-            }
-            if query.just_released(PlatformerAction::Up) {
-                info!("Up");
-            }
+        // healing keeps the player in place, crouching slows them down
+        let input = match state_machine.0.state() {
+            State::Healing {} => 0.,
+            State::Crouching {} => horizontal_input(action_state) * settings.crouch_speed,
+            _ => horizontal_input(action_state),
+        };
+        let input_locked = movement.input_locked(time.elapsed());
+        if input != 0. && !input_locked {
+            movement.facing = input.signum();
         }
 
+        // the dash system drives the velocity while dashing, a wall jump's kick
+        // is kept until its lockout is over
+        if !matches!(state_machine.0.state(), State::Dashing {}) && !input_locked {
+            let grounded = matches!(
+                state_machine.0.state(),
+                State::Idle {} | State::Healing {} | State::Crouching {}
+            );
+            movement.velocity = run(
+                movement.velocity,
+                input,
+                settings,
+                grounded,
+                time.delta_seconds(),
+            );
+        }
 
+        let distance = movement.velocity * time.delta_seconds();
+        let height = jump.velocity * time.delta_seconds();
 
-        for mut character_controller in character_controllers.iter_mut() {
-            let right = if input.pressed(KeyCode::D) { 1. } else { 0. };
-            let left = if input.pressed(KeyCode::A) { 1. } else { 0. };
-            let distance = (right - left) * MOVEMENT_SPEED;
-            let mut height = 0.; 
-            let char_pos = character_controller.translation.unwrap_or_default();
-//           character_controller.translation = Some(Vec2::new(distance, -1.));
+        // ride along with whatever moving platform we are standing on
+        let carried = ground_sensors
+            .iter()
+            .filter(|sensor| sensor.ground_detection_entity == player)
+            .flat_map(|sensor| &sensor.intersecting_ground_entities)
+            .find_map(|&ground| moving_platforms.get(ground).ok())
+            .map_or(Vec2::ZERO, |platform| platform.displacement);
 
- //           if input.just_pressed(KeyCode::Space) {
-                //ENTER JUMP STATE
-                //IF IN JUMP STATE, LERP TOWARDS JUMP HEIGHT instead of just moving there instantly
-                //EXIT JUMP STATE WHEN GROUNDED
-//                character_controller.translation = Some(Vec2::new(0., JUMP_HEIGHT));
-  //          }
-            //
+        character_controller.translation = Some(Vec2::new(distance, height) + carried);
+    }
+}
 
-        for state in state.iter_mut() {
-            match state.0.state() {
-                State::Idle {} => {
-                    height = char_pos.y - 0.1;
-                }
-                State::Jumping {} => {
-                    jump.time.tick(time.delta());
-                    let jump_height = 10.; 
-                    println!("jump high: {}", jump_height);
-                    println!("elapsed: {}", jump.time.elapsed_secs());
-                    println!("distance: {}", distance);
-                    height = jump_height;
-                }
-                State::Falling {} => {
-                    height = char_pos.y - 0.1;
-                    jump.time.reset();
-                }
-            }
-        }
+/// Which way the player wants to move, from -1 (left) to 1 (right). The
+/// Left / Right buttons win over the stick when both are used.
+pub fn horizontal_input(action_state: &ActionState<PlatformerAction>) -> f32 {
+    let right = if action_state.pressed(PlatformerAction::Right) { 1. } else { 0. };
+    let left = if action_state.pressed(PlatformerAction::Left) { 1. } else { 0. };
 
-            character_controller.translation = Some(Vec2::new(distance, height));
-        }
+    if right != left {
+        right - left
+    } else {
+        action_state.clamped_value(PlatformerAction::Horizontal)
     }
 }
 
+/// Moves `velocity` towards the speed asked for by `input`, speeding up with the
+/// acceleration and slowing down or turning with the deceleration.
+fn run(
+    velocity: f32,
+    input: f32,
+    settings: &MovementSettings,
+    grounded: bool,
+    delta_seconds: f32,
+) -> f32 {
+    let target = input * settings.max_speed;
+    let speeding_up = target.abs() > velocity.abs() && target * velocity >= 0.;
+    let rate = if speeding_up {
+        settings.acceleration
+    } else {
+        settings.deceleration
+    };
+    let control = if grounded { 1. } else { settings.air_control };
+
+    let step = rate * control * delta_seconds;
+    velocity + (target - velocity).clamp(-step, step)
+}
 
 #[derive(Default)]
 struct PlayerStateMachine {
     last_jump: Option<Duration>,
+    /// When the player walked off the ground, cleared once a jump uses it.
+    left_ground: Option<Duration>,
+    /// When Jump was pressed without being able to jump.
+    buffered_jump: Option<Duration>,
+    coyote_time: Duration,
+    jump_buffer: Duration,
+    /// Set on every transition until `PlayerState::take_transition` reads it.
+    transitioned: bool,
+}
+
+impl PlayerStateMachine {
+    fn start_jump(&mut self, event_time: Duration) -> Response<State> {
+        self.last_jump = Some(event_time);
+        self.left_ground = None;
+        self.buffered_jump = None;
+        Transition(State::jumping())
+    }
+
+    /// Back on the ground, jumping right away if a jump was buffered.
+    fn land(&mut self, event_time: Duration) -> Response<State> {
+        self.left_ground = None;
+        if Self::within(self.jump_buffer, self.buffered_jump, event_time) {
+            self.start_jump(event_time)
+        } else {
+            self.buffered_jump = None;
+            Transition(State::idle())
+        }
+    }
+
+    fn within(window: Duration, since: Option<Duration>, now: Duration) -> bool {
+        since.is_some_and(|since| now.saturating_sub(since) <= window)
+    }
 }
+
 #[derive(Debug)]
 pub enum Event {
     Jump { event_time: Duration, rising: bool, peak_reached: bool},
-    Land,
-    Fall,
+    /// Forces a rising jump from any state, e.g. when bounced by a spring.
+    Launch { event_time: Duration },
+    Land { event_time: Duration },
+    Fall { event_time: Duration },
+    /// Starts a dash, sent once the dash system has checked it is allowed.
+    Dash,
+    DashEnd { event_time: Duration, grounded: bool },
+    /// Starts healing, sent once the heal system has checked it is possible.
+    Heal,
+    HealEnd,
+    Crouch,
+    /// Stands up again, sent once the crouch system has checked there is room.
+    Stand,
+    /// Starts sliding down the wall the player is falling against.
+    WallSlide,
 }
 
 #[derive(Component)]
@@ -291,16 +361,56 @@ pub struct PlayerState(
     InitializedStatemachine<PlayerStateMachine>,
 );
 
+impl PlayerState {
+    pub fn handle(&mut self, event: &Event) {
+        self.0.handle(event);
+    }
+
+    pub fn state(&self) -> &State {
+        self.0.state()
+    }
+
+    /// Whether the machine changed state since this was last called. A new
+    /// machine counts as having just entered its initial state.
+    pub fn take_transition(&mut self) -> bool {
+        std::mem::take(&mut self.0.transitioned)
+    }
+
+    pub fn new(coyote_time: Duration, jump_buffer: Duration) -> Self {
+        Self(
+            PlayerStateMachine {
+                coyote_time,
+                jump_buffer,
+                transitioned: true,
+                ..Default::default()
+            }
+            .state_machine()
+            .init(),
+        )
+    }
+}
+
 impl Default for PlayerState {
     fn default() -> Self {
-        Self(
-            PlayerStateMachine::default()
-                .state_machine()
-                .init(),
+        JumpSettings::default().into()
+    }
+}
+
+impl From<JumpSettings> for PlayerState {
+    fn from(settings: JumpSettings) -> Self {
+        Self::new(
+            Duration::from_secs_f32(settings.coyote_time),
+            Duration::from_secs_f32(settings.jump_buffer_time),
         )
     }
 }
 
+impl From<&EntityInstance> for PlayerState {
+    fn from(entity_instance: &EntityInstance) -> Self {
+        JumpSettings::from(entity_instance).into()
+    }
+}
+
 
 #[state_machine(
     initial = "State::idle()",
@@ -315,6 +425,7 @@ impl PlayerStateMachine {
         source: &State,
         target: &State,
     ) {
+        self.transitioned = true;
         info!(
             "transitioned from `{:?}` to `{:?}`",
             source, target
@@ -334,103 +445,359 @@ impl PlayerStateMachine {
     #[state]
     fn idle(&mut self, event: &Event) -> Response<State> {
         match event {
-            Event::Jump { event_time, peak_reached, rising} => {
-                self.last_jump = Some(*event_time);
-                Transition(State::jumping())
+            Event::Jump { event_time, .. } | Event::Launch { event_time } => {
+                self.start_jump(*event_time)
+            }
+            Event::Land { .. } => Transition(State::idle()),
+            Event::Fall { event_time } => {
+                self.left_ground = Some(*event_time);
+                Transition(State::falling())
+            }
+            Event::Dash => Transition(State::dashing()),
+            Event::Heal => Transition(State::healing()),
+            Event::Crouch => Transition(State::crouching()),
+            Event::DashEnd { .. } | Event::HealEnd | Event::Stand | Event::WallSlide => Handled,
+        }
+    }
+    #[state]
+    fn jumping(&mut self, event: &Event) -> Response<State> {
+        match event {
+            Event::Jump { peak_reached: true, .. } | Event::Jump { rising: false, .. } => {
+                Transition(State::falling())
+            }
+            Event::Jump { event_time, .. } => {
+                self.buffered_jump = Some(*event_time);
+                Handled
             }
-            Event::Land => Transition(State::idle()),
-            Event::Fall => Transition(State::falling()),
+            Event::Launch { event_time } => self.start_jump(*event_time),
+            Event::Land { .. } => Transition(State::idle()),
+            Event::Fall { .. } => Transition(State::falling()),
+            Event::Dash => Transition(State::dashing()),
+            Event::DashEnd { .. }
+            | Event::Heal
+            | Event::HealEnd
+            | Event::Crouch
+            | Event::Stand
+            | Event::WallSlide => Handled,
         }
     }
     #[state]
-    fn jumping(event: &Event) -> Response<State> {
+    fn falling(&mut self, event: &Event) -> Response<State> {
         match event {
-            Event::Jump { event_time, peak_reached, rising} => {
-                Transition(State::jumping())
+            Event::Jump { event_time, .. } => {
+                if Self::within(self.coyote_time, self.left_ground, *event_time) {
+                    self.start_jump(*event_time)
+                } else {
+                    self.buffered_jump = Some(*event_time);
+                    Handled
+                }
+            }
+            Event::Launch { event_time } => self.start_jump(*event_time),
+            Event::Land { event_time } => self.land(*event_time),
+            Event::Fall { .. } => Transition(State::falling()),
+            Event::Dash => {
+                // no coyote jump after dashing off a ledge
+                self.left_ground = None;
+                Transition(State::dashing())
             }
-            Event::Land => Transition(State::idle()),
-            Event::Fall => Transition(State::falling()),
+            Event::WallSlide => {
+                self.left_ground = None;
+                Transition(State::wall_sliding())
+            }
+            Event::DashEnd { .. }
+            | Event::Heal
+            | Event::HealEnd
+            | Event::Crouch
+            | Event::Stand => Handled,
         }
     }
+    // Gravity is suspended while dashing, a jump pressed meanwhile is buffered.
     #[state]
-    fn falling(event: &Event) -> Response<State> {
+    fn dashing(&mut self, event: &Event) -> Response<State> {
         match event {
-            Event::Jump { event_time, peak_reached, rising} => {
-                Transition(State::jumping())
+            Event::Jump { event_time, .. } => {
+                self.buffered_jump = Some(*event_time);
+                Handled
             }
-            Event::Land => Transition(State::idle()),
-            Event::Fall => Transition(State::falling()),
-
+            Event::Launch { event_time } => self.start_jump(*event_time),
+            Event::DashEnd {
+                event_time,
+                grounded: true,
+            } => self.land(*event_time),
+            Event::DashEnd { .. } => Transition(State::falling()),
+            Event::Land { .. }
+            | Event::Fall { .. }
+            | Event::Dash
+            | Event::Heal
+            | Event::HealEnd
+            | Event::Crouch
+            | Event::Stand
+            | Event::WallSlide => Handled,
+        }
+    }
+    // Standing still while Heal is held, anything that moves the player stops it.
+    #[state]
+    fn healing(&mut self, event: &Event) -> Response<State> {
+        match event {
+            Event::Jump { event_time, .. } | Event::Launch { event_time } => {
+                self.start_jump(*event_time)
+            }
+            Event::Fall { event_time } => {
+                self.left_ground = Some(*event_time);
+                Transition(State::falling())
+            }
+            Event::Dash => Transition(State::dashing()),
+            Event::HealEnd => Transition(State::idle()),
+            Event::Land { .. }
+            | Event::DashEnd { .. }
+            | Event::Heal
+            | Event::Crouch
+            | Event::Stand
+            | Event::WallSlide => Handled,
+        }
+    }
+    // Jump does nothing while crouched, so the player never jumps into a low
+    // ceiling, Down + Jump still drops through one-way platforms.
+    #[state]
+    fn crouching(&mut self, event: &Event) -> Response<State> {
+        match event {
+            Event::Launch { event_time } => self.start_jump(*event_time),
+            Event::Fall { event_time } => {
+                self.left_ground = Some(*event_time);
+                Transition(State::falling())
+            }
+            Event::Dash => Transition(State::dashing()),
+            Event::Stand => Transition(State::idle()),
+            Event::Jump { .. }
+            | Event::Land { .. }
+            | Event::DashEnd { .. }
+            | Event::Heal
+            | Event::HealEnd
+            | Event::Crouch
+            | Event::WallSlide => Handled,
+        }
+    }
+    // Falling no faster than the wall slide speed, Jump kicks off the wall.
+    #[state]
+    fn wall_sliding(&mut self, event: &Event) -> Response<State> {
+        match event {
+            Event::Jump { event_time, .. } | Event::Launch { event_time } => {
+                self.start_jump(*event_time)
+            }
+            Event::Land { event_time } => self.land(*event_time),
+            Event::Fall { .. } => Transition(State::falling()),
+            Event::Dash => Transition(State::dashing()),
+            Event::DashEnd { .. }
+            | Event::Heal
+            | Event::HealEnd
+            | Event::Crouch
+            | Event::Stand
+            | Event::WallSlide => Handled,
         }
     }
 }
 
+#[cfg(test)]
+mod tests {
+    use super::*;
 
-//This shit is copied straight from the ldtk example. It's in here because it tracks the player,
-//could probably be moved to a separate camera plugin.
-const ASPECT_RATIO: f32 = 16. / 9.;
+    const COYOTE_TIME: Duration = Duration::from_millis(100);
+    const JUMP_BUFFER: Duration = Duration::from_millis(120);
 
-#[allow(clippy::type_complexity)]
-pub fn camera_fit_inside_current_level(
-    mut camera_query: Query<
-        (
-            &mut bevy::render::camera::OrthographicProjection,
-            &mut Transform,
-        ),
-        Without<Player>,
-    >,
-    player_query: Query<&Transform, With<Player>>,
-    level_query: Query<(&Transform, &LevelIid), (Without<OrthographicProjection>, Without<Player>)>,
-    ldtk_projects: Query<&Handle<LdtkProject>>,
-    level_selection: Res<LevelSelection>,
-    ldtk_project_assets: Res<Assets<LdtkProject>>,
-) {
-    if let Ok(Transform {
-        translation: player_translation,
-        ..
-    }) = player_query.get_single()
-    {
-        let player_translation = *player_translation;
-
-        let (mut orthographic_projection, mut camera_transform) = camera_query.single_mut();
-
-        for (level_transform, level_iid) in &level_query {
-            let ldtk_project = ldtk_project_assets
-                .get(ldtk_projects.single())
-                .expect("Project should be loaded if level has spawned");
-
-            let level = ldtk_project
-                .get_raw_level_by_iid(&level_iid.to_string())
-                .expect("Spawned level should exist in LDtk project");
-
-            if level_selection.is_match(&LevelIndices::default(), level) {
-                let level_ratio = level.px_wid as f32 / level.px_hei as f32;
-                orthographic_projection.viewport_origin = Vec2::ZERO;
-                if level_ratio > ASPECT_RATIO {
-                    // level is wider than the screen
-                    let height = (level.px_hei as f32 / 9.).round() * 9.;
-                    let width = height * ASPECT_RATIO;
-                    orthographic_projection.scaling_mode =
-                        bevy::render::camera::ScalingMode::Fixed { width, height };
-                    camera_transform.translation.x =
-                        (player_translation.x - level_transform.translation.x - width / 2.)
-                            .clamp(0., level.px_wid as f32 - width);
-                    camera_transform.translation.y = 0.;
-                } else {
-                    // level is taller than the screen
-                    let width = (level.px_wid as f32 / 16.).round() * 16.;
-                    let height = width / ASPECT_RATIO;
-                    orthographic_projection.scaling_mode =
-                        bevy::render::camera::ScalingMode::Fixed { width, height };
-                    camera_transform.translation.y =
-                        (player_translation.y - level_transform.translation.y - height / 2.)
-                            .clamp(0., level.px_hei as f32 - height);
-                    camera_transform.translation.x = 0.;
-                }
+    fn ms(millis: u64) -> Duration {
+        Duration::from_millis(millis)
+    }
 
-                camera_transform.translation.x += level_transform.translation.x;
-                camera_transform.translation.y += level_transform.translation.y;
-            }
-        }
+    fn press_jump(state: &mut PlayerState, event_time: Duration) {
+        state.0.handle(&Event::Jump {
+            event_time,
+            rising: true,
+            peak_reached: false,
+        });
+    }
+
+    fn falling_after_jump() -> PlayerState {
+        let mut state = PlayerState::new(COYOTE_TIME, JUMP_BUFFER);
+        press_jump(&mut state, ms(0));
+        state.0.handle(&Event::Jump {
+            event_time: ms(400),
+            rising: false,
+            peak_reached: true,
+        });
+        assert!(matches!(state.0.state(), State::Falling {}));
+        state
+    }
+
+    #[test]
+    fn jump_inside_coyote_window_after_walking_off_ledge() {
+        let mut state = PlayerState::new(COYOTE_TIME, JUMP_BUFFER);
+        state.0.handle(&Event::Fall { event_time: ms(1000) });
+        press_jump(&mut state, ms(1080));
+
+        assert!(matches!(state.0.state(), State::Jumping {}));
+        assert_eq!(state.0.last_jump, Some(ms(1080)));
+    }
+
+    #[test]
+    fn jump_after_coyote_window_is_not_a_jump() {
+        let mut state = PlayerState::new(COYOTE_TIME, JUMP_BUFFER);
+        state.0.handle(&Event::Fall { event_time: ms(1000) });
+        press_jump(&mut state, ms(1150));
+
+        assert!(matches!(state.0.state(), State::Falling {}));
+        assert_eq!(state.0.last_jump, None);
+    }
+
+    #[test]
+    fn no_coyote_time_when_falling_from_a_jump() {
+        let mut state = falling_after_jump();
+        press_jump(&mut state, ms(410));
+
+        assert!(matches!(state.0.state(), State::Falling {}));
+        assert_eq!(state.0.last_jump, Some(ms(0)));
+    }
+
+    #[test]
+    fn buffered_jump_fires_on_landing() {
+        let mut state = falling_after_jump();
+        press_jump(&mut state, ms(900));
+        state.0.handle(&Event::Land { event_time: ms(1000) });
+
+        assert!(matches!(state.0.state(), State::Jumping {}));
+        assert_eq!(state.0.last_jump, Some(ms(1000)));
+    }
+
+    #[test]
+    fn press_while_rising_is_buffered_until_landing() {
+        let mut state = PlayerState::new(COYOTE_TIME, JUMP_BUFFER);
+        press_jump(&mut state, ms(0));
+        press_jump(&mut state, ms(50));
+        state.0.handle(&Event::Jump {
+            event_time: ms(100),
+            rising: false,
+            peak_reached: true,
+        });
+        state.0.handle(&Event::Land { event_time: ms(150) });
+
+        assert!(matches!(state.0.state(), State::Jumping {}));
+        assert_eq!(state.0.last_jump, Some(ms(150)));
+    }
+
+    #[test]
+    fn stale_buffered_jump_is_dropped_on_landing() {
+        let mut state = falling_after_jump();
+        press_jump(&mut state, ms(800));
+        state.0.handle(&Event::Land { event_time: ms(1000) });
+
+        assert!(matches!(state.0.state(), State::Idle {}));
+
+        // the stale press must not fire on a later landing either
+        state.0.handle(&Event::Fall { event_time: ms(2000) });
+        state.0.handle(&Event::Land { event_time: ms(2010) });
+        assert!(matches!(state.0.state(), State::Idle {}));
+    }
+
+    #[test]
+    fn jump_pressed_during_a_dash_fires_when_it_ends_on_the_ground() {
+        let mut state = PlayerState::new(COYOTE_TIME, JUMP_BUFFER);
+        state.0.handle(&Event::Dash);
+        press_jump(&mut state, ms(1050));
+        assert!(matches!(state.0.state(), State::Dashing {}));
+
+        state.0.handle(&Event::DashEnd {
+            event_time: ms(1100),
+            grounded: true,
+        });
+        assert!(matches!(state.0.state(), State::Jumping {}));
+        assert_eq!(state.0.last_jump, Some(ms(1100)));
+    }
+
+    #[test]
+    fn air_dash_ends_falling_without_coyote_time() {
+        let mut state = PlayerState::new(COYOTE_TIME, JUMP_BUFFER);
+        state.0.handle(&Event::Fall { event_time: ms(1000) });
+        state.0.handle(&Event::Dash);
+        state.0.handle(&Event::DashEnd {
+            event_time: ms(1020),
+            grounded: false,
+        });
+        assert!(matches!(state.0.state(), State::Falling {}));
+
+        press_jump(&mut state, ms(1030));
+        assert!(matches!(state.0.state(), State::Falling {}));
+    }
+
+    #[test]
+    fn jumping_or_walking_off_a_ledge_stops_healing() {
+        let mut state = PlayerState::new(COYOTE_TIME, JUMP_BUFFER);
+        state.0.handle(&Event::Heal);
+        assert!(matches!(state.0.state(), State::Healing {}));
+        press_jump(&mut state, ms(500));
+        assert!(matches!(state.0.state(), State::Jumping {}));
+
+        let mut state = PlayerState::new(COYOTE_TIME, JUMP_BUFFER);
+        state.0.handle(&Event::Heal);
+        state.0.handle(&Event::Fall { event_time: ms(500) });
+        assert!(matches!(state.0.state(), State::Falling {}));
+        assert_eq!(state.0.left_ground, Some(ms(500)));
+    }
+
+    #[test]
+    fn jump_is_ignored_while_crouching_until_standing_up() {
+        let mut state = PlayerState::new(COYOTE_TIME, JUMP_BUFFER);
+        state.0.handle(&Event::Crouch);
+        assert!(matches!(state.0.state(), State::Crouching {}));
+        press_jump(&mut state, ms(500));
+        assert!(matches!(state.0.state(), State::Crouching {}));
+
+        state.0.handle(&Event::Stand);
+        press_jump(&mut state, ms(600));
+        assert!(matches!(state.0.state(), State::Jumping {}));
+    }
+
+    #[test]
+    fn jumping_off_a_wall_slide_and_falling_away_from_it() {
+        let mut state = falling_after_jump();
+        state.0.handle(&Event::WallSlide);
+        assert!(matches!(state.0.state(), State::WallSliding {}));
+        press_jump(&mut state, ms(600));
+        assert!(matches!(state.0.state(), State::Jumping {}));
+        assert_eq!(state.0.last_jump, Some(ms(600)));
+
+        // no coyote jump after letting go of the wall
+        let mut state = PlayerState::new(COYOTE_TIME, JUMP_BUFFER);
+        state.0.handle(&Event::Fall { event_time: ms(0) });
+        state.0.handle(&Event::WallSlide);
+        state.0.handle(&Event::Fall { event_time: ms(20) });
+        press_jump(&mut state, ms(40));
+        assert!(matches!(state.0.state(), State::Falling {}));
+    }
+
+    fn run_for(seconds: f32, frames_per_second: f32, input: f32, grounded: bool) -> f32 {
+        let settings = MovementSettings::default();
+        let frames = (seconds * frames_per_second).round() as usize;
+        (0..frames).fold(0., |velocity, _| {
+            run(velocity, input, &settings, grounded, 1. / frames_per_second)
+        })
+    }
+
+    #[test]
+    fn running_speed_does_not_depend_on_framerate() {
+        let settings = MovementSettings::default();
+
+        let slow = run_for(0.1, 30., 1., true);
+        let fast = run_for(0.1, 240., 1., true);
+
+        assert!((slow - fast).abs() < 0.01);
+        assert!((slow - settings.acceleration * 0.1).abs() < 0.01);
+    }
+
+    #[test]
+    fn running_stops_at_max_speed_and_air_control_is_weaker() {
+        let settings = MovementSettings::default();
+
+        assert_eq!(run_for(2., 60., -1., true), -settings.max_speed);
+        assert_eq!(run_for(2., 60., 0.5, true), settings.max_speed / 2.);
+        assert!(run_for(0.05, 60., 1., false) < run_for(0.05, 60., 1., true));
     }
 }
@@ -4,11 +4,13 @@ use bevy_rapier2d::prelude::*;
 use bevy_ecs_ldtk::{
     prelude::*
 };
-use crate::actions::PlatformerAction;
+use crate::actions::{default_input_map, PlatformerAction};
 use crate::coin::Wallet;
 use crate::components::ColliderBundle;
-use crate::components::GroundDetection;
+use crate::components::{GroundDetection, WallDetection};
+use crate::health::{HealSettings, Health};
 use bevy::time::Stopwatch;
+use std::time::Duration;
 use leafwing_input_manager::prelude::*;
 use crate::player::PlayerState;
 
@@ -18,6 +20,291 @@ pub struct JumpState {
     pub time: Stopwatch,
     pub rising: bool,
     pub peak_reached: bool,
+    /// Current vertical velocity in pixels per second, positive is up.
+    pub velocity: f32,
+}
+
+/// Tunables for the jump arc.
+///
+/// Every value can be overridden per player with a float field of the same
+/// name on the LDtk `Player` entity, so the feel can be tuned from the editor.
+/// The other player settings are read from the `Player` entity the same way,
+/// through `float_field`.
+#[derive(Clone, Copy, Debug, Component)]
+pub struct JumpSettings {
+    /// Height of a full (held) jump, in pixels.
+    pub jump_height: f32,
+    /// Seconds from takeoff to the apex of a full jump.
+    pub time_to_apex: f32,
+    /// Gravity multiplier applied while falling, > 1 for a snappier descent.
+    pub fall_gravity_multiplier: f32,
+    /// Fraction of the upward velocity kept when Jump is released early.
+    pub jump_cut_multiplier: f32,
+    /// Terminal fall speed, in pixels per second.
+    pub max_fall_speed: f32,
+    /// Seconds after walking off a ledge during which a jump is still allowed.
+    pub coyote_time: f32,
+    /// Seconds a Jump press is remembered while airborne, firing on landing.
+    pub jump_buffer_time: f32,
+}
+
+impl JumpSettings {
+    /// Gravity needed to reach `jump_height` in `time_to_apex`, in pixels per second squared.
+    pub fn gravity(&self) -> f32 {
+        2. * self.jump_height / (self.time_to_apex * self.time_to_apex)
+    }
+
+    /// Initial upward velocity of a jump, in pixels per second.
+    pub fn jump_velocity(&self) -> f32 {
+        2. * self.jump_height / self.time_to_apex
+    }
+}
+
+impl Default for JumpSettings {
+    fn default() -> Self {
+        Self {
+            jump_height: 64.,
+            time_to_apex: 0.4,
+            fall_gravity_multiplier: 1.5,
+            jump_cut_multiplier: 0.5,
+            max_fall_speed: 480.,
+            coyote_time: 0.1,
+            jump_buffer_time: 0.12,
+        }
+    }
+}
+
+/// Shortest duration accepted from LDtk for settings that are divided by,
+/// so a zero entered in the editor can't turn velocities into inf or NaN.
+pub const MIN_DURATION: f32 = 0.01;
+
+/// The float field `identifier` of an LDtk entity, or `default` if it has none.
+pub fn float_field(entity_instance: &EntityInstance, identifier: &str, default: f32) -> f32 {
+    entity_instance
+        .get_float_field(identifier)
+        .copied()
+        .unwrap_or(default)
+}
+
+impl From<&EntityInstance> for JumpSettings {
+    fn from(entity_instance: &EntityInstance) -> Self {
+        let defaults = JumpSettings::default();
+
+        JumpSettings {
+            jump_height: float_field(entity_instance, "jump_height", defaults.jump_height),
+            time_to_apex: float_field(entity_instance, "time_to_apex", defaults.time_to_apex)
+                .max(MIN_DURATION),
+            fall_gravity_multiplier: float_field(
+                entity_instance,
+                "fall_gravity_multiplier",
+                defaults.fall_gravity_multiplier,
+            ),
+            jump_cut_multiplier: float_field(
+                entity_instance,
+                "jump_cut_multiplier",
+                defaults.jump_cut_multiplier,
+            ),
+            max_fall_speed: float_field(entity_instance, "max_fall_speed", defaults.max_fall_speed),
+            coyote_time: float_field(entity_instance, "coyote_time", defaults.coyote_time),
+            jump_buffer_time: float_field(
+                entity_instance,
+                "jump_buffer_time",
+                defaults.jump_buffer_time,
+            ),
+        }
+    }
+}
+
+#[derive(Component)]
+pub struct MovementState {
+    /// Current horizontal velocity in pixels per second, positive is right.
+    pub velocity: f32,
+    /// 1 when the player last moved right, -1 when they last moved left.
+    pub facing: f32,
+    /// Horizontal input is ignored until then, e.g. right after a wall jump.
+    pub input_locked_until: Duration,
+}
+
+impl MovementState {
+    pub fn input_locked(&self, now: Duration) -> bool {
+        now < self.input_locked_until
+    }
+}
+
+impl Default for MovementState {
+    fn default() -> Self {
+        Self {
+            velocity: 0.,
+            facing: 1.,
+            input_locked_until: Duration::ZERO,
+        }
+    }
+}
+
+/// Tunables for running left and right.
+#[derive(Clone, Copy, Debug, Component)]
+pub struct MovementSettings {
+    /// Top running speed with the stick or button fully held, in pixels per second.
+    pub max_speed: f32,
+    /// How quickly the player speeds up, in pixels per second squared.
+    pub acceleration: f32,
+    /// How quickly the player slows down or turns around, in pixels per second squared.
+    pub deceleration: f32,
+    /// Fraction of the acceleration and deceleration available in the air.
+    pub air_control: f32,
+    /// Fraction of `max_speed` left while crouching.
+    pub crouch_speed: f32,
+    /// Fraction of the standing collider height left while crouching.
+    pub crouch_height: f32,
+}
+
+impl Default for MovementSettings {
+    fn default() -> Self {
+        Self {
+            max_speed: 90.,
+            acceleration: 720.,
+            deceleration: 960.,
+            air_control: 0.5,
+            crouch_speed: 0.4,
+            crouch_height: 0.6,
+        }
+    }
+}
+
+impl From<&EntityInstance> for MovementSettings {
+    fn from(entity_instance: &EntityInstance) -> Self {
+        let defaults = MovementSettings::default();
+
+        MovementSettings {
+            max_speed: float_field(entity_instance, "max_speed", defaults.max_speed),
+            acceleration: float_field(entity_instance, "acceleration", defaults.acceleration),
+            deceleration: float_field(entity_instance, "deceleration", defaults.deceleration),
+            air_control: float_field(entity_instance, "air_control", defaults.air_control),
+            crouch_speed: float_field(entity_instance, "crouch_speed", defaults.crouch_speed),
+            crouch_height: float_field(entity_instance, "crouch_height", defaults.crouch_height),
+        }
+    }
+}
+
+/// Tunables for sliding down and jumping off walls.
+#[derive(Clone, Copy, Debug, Component)]
+pub struct WallSettings {
+    /// Fastest the player slides down a wall, in pixels per second.
+    pub wall_slide_speed: f32,
+    /// Horizontal speed a wall jump kicks the player away with, in pixels per second.
+    pub wall_jump_speed: f32,
+    /// Seconds after a wall jump during which left / right are ignored.
+    pub wall_jump_lockout: f32,
+}
+
+impl Default for WallSettings {
+    fn default() -> Self {
+        Self {
+            wall_slide_speed: 60.,
+            wall_jump_speed: 150.,
+            wall_jump_lockout: 0.15,
+        }
+    }
+}
+
+impl From<&EntityInstance> for WallSettings {
+    fn from(entity_instance: &EntityInstance) -> Self {
+        let defaults = WallSettings::default();
+
+        WallSettings {
+            wall_slide_speed: float_field(
+                entity_instance,
+                "wall_slide_speed",
+                defaults.wall_slide_speed,
+            ),
+            wall_jump_speed: float_field(
+                entity_instance,
+                "wall_jump_speed",
+                defaults.wall_jump_speed,
+            ),
+            wall_jump_lockout: float_field(
+                entity_instance,
+                "wall_jump_lockout",
+                defaults.wall_jump_lockout,
+            ),
+        }
+    }
+}
+
+#[derive(Component, Default)]
+pub struct DashState {
+    /// When the current or last dash started.
+    pub started: Option<Duration>,
+    /// 1 for a dash to the right, -1 to the left.
+    pub direction: f32,
+    pub timer: Timer,
+    /// Dashes left before touching the ground again.
+    pub air_dashes_left: u32,
+    pub afterimage: Timer,
+}
+
+/// Tunables for the dash. `air_dashes` is an integer field on the LDtk
+/// `Player` entity, the rest are floats.
+#[derive(Clone, Copy, Debug, Component)]
+pub struct DashSettings {
+    /// How far a dash goes, in pixels.
+    pub dash_distance: f32,
+    /// How long a dash takes, in seconds.
+    pub dash_duration: f32,
+    /// Seconds from the start of one dash until the next one is allowed.
+    pub dash_cooldown: f32,
+    /// Dashes allowed between leaving the ground and landing again.
+    pub air_dashes: u32,
+    /// Seconds between afterimages left behind while dashing.
+    pub afterimage_interval: f32,
+    /// Seconds an afterimage takes to fade out.
+    pub afterimage_lifetime: f32,
+}
+
+impl DashSettings {
+    /// Horizontal speed while dashing, in pixels per second.
+    pub fn dash_speed(&self) -> f32 {
+        self.dash_distance / self.dash_duration
+    }
+}
+
+impl Default for DashSettings {
+    fn default() -> Self {
+        Self {
+            dash_distance: 48.,
+            dash_duration: 0.15,
+            dash_cooldown: 0.4,
+            air_dashes: 1,
+            afterimage_interval: 0.03,
+            afterimage_lifetime: 0.2,
+        }
+    }
+}
+
+impl From<&EntityInstance> for DashSettings {
+    fn from(entity_instance: &EntityInstance) -> Self {
+        let defaults = DashSettings::default();
+
+        DashSettings {
+            dash_distance: float_field(entity_instance, "dash_distance", defaults.dash_distance),
+            dash_duration: float_field(entity_instance, "dash_duration", defaults.dash_duration)
+                .max(MIN_DURATION),
+            dash_cooldown: float_field(entity_instance, "dash_cooldown", defaults.dash_cooldown),
+            air_dashes: entity_instance
+                .get_int_field("air_dashes")
+                .map_or(defaults.air_dashes, |&air_dashes| air_dashes.max(0) as u32),
+            afterimage_interval: float_field(
+                entity_instance,
+                "afterimage_interval",
+                defaults.afterimage_interval,
+            ),
+            afterimage_lifetime: float_field(
+                entity_instance,
+                "afterimage_lifetime",
+                defaults.afterimage_lifetime,
+            ),
+        }
+    }
 }
 
 #[derive(Clone, Default, Copy, Eq, PartialEq, Debug, Component)]
@@ -35,9 +322,25 @@ pub struct PlayerBundle {
     #[worldly]
     pub worldly: Worldly,
     pub ground_detection: GroundDetection,
+    pub wall_detection: WallDetection,
     pub controller: KinematicCharacterController,
+    #[from_entity_instance]
     pub state: PlayerState,
     pub jump_duration: JumpState,
+    #[from_entity_instance]
+    pub jump_settings: JumpSettings,
+    pub movement: MovementState,
+    #[from_entity_instance]
+    pub movement_settings: MovementSettings,
+    #[from_entity_instance]
+    pub wall_settings: WallSettings,
+    pub dash: DashState,
+    #[from_entity_instance]
+    pub dash_settings: DashSettings,
+    #[from_entity_instance]
+    pub health: Health,
+    #[from_entity_instance]
+    pub heal_settings: HealSettings,
     // The whole EntityInstance can be stored directly as an EntityInstance component
     #[from_entity_instance]
     entity_instance: EntityInstance,
@@ -54,45 +357,9 @@ pub struct PlayerInput {
 }
 impl Default for PlayerInput {
     fn default() -> Self {
-        use PlatformerAction::*;
-
-        let mut input_map = InputMap::default();
-
-        // basic movement
-        input_map.insert(KeyCode::W, Up);
-        input_map.insert(GamepadButtonType::DPadUp, Up);
-
-        input_map.insert(KeyCode::S, Down);
-        input_map.insert(GamepadButtonType::DPadDown, Down);
-
-        input_map.insert(KeyCode::A, Left);
-        input_map.insert(GamepadButtonType::DPadLeft, Left);
-
-        input_map.insert(
-            SingleAxis::symmetric(GamepadAxisType::LeftStickX, 0.1),
-            Horizontal,
-        );
-
-        input_map.insert(KeyCode::D, Right);
-        input_map.insert(GamepadButtonType::DPadRight, Right);
-
-        // Jump
-        input_map.insert(KeyCode::Space, PlatformerAction::Jump);
-        input_map.insert(GamepadButtonType::South, PlatformerAction::Jump);
-
-        input_map.insert(KeyCode::E, PlatformerAction::Dash);
-        input_map.insert(GamepadButtonType::RightTrigger2, PlatformerAction::Dash);
-
-        input_map.insert(KeyCode::Return, PlatformerAction::Pause);
-        input_map.insert(GamepadButtonType::Start, PlatformerAction::Pause);
-
-        input_map.insert(KeyCode::I, PlatformerAction::Menus);
-        input_map.insert(GamepadButtonType::Select, PlatformerAction::Menus);
-        input_map.set_gamepad(Gamepad { id: 0 });
-
         Self {
             input: InputManagerBundle::<PlatformerAction> {
-                input_map,
+                input_map: default_input_map(),
                 ..Default::default()
             },
         }
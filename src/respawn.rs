@@ -1,31 +1,73 @@
 use bevy::prelude::*;
 use bevy_ecs_ldtk::prelude::*;
-use crate::events::RestartLevelEvent;
+use crate::checkpoint::LastCheckpoint;
+use crate::coin::Wallet;
+use crate::death::Dying;
+use crate::events::{PlayerRespawned, RestartLevelEvent, RestartScope};
+use crate::player::PlayerState;
+use crate::player_components::{DashState, JumpSettings, JumpState, MovementState, Player};
 
 pub struct RespawnPlugin;
 
 impl Plugin for RespawnPlugin {
     fn build(&self, app: &mut App) {
-        app.add_systems(Update, respawn_world);
+        app.add_systems(
+            Update,
+            (store_spawn_point, respawn_level, respawn_world, respawn_player).chain(),
+        );
     }
 }
 
+/// Where LDtk placed the player and what they carried into the level, restored
+/// when the level starts over.
+#[derive(Clone, Component)]
+pub struct SpawnPoint {
+    pub transform: Transform,
+    pub wallet: Wallet,
+}
+
+pub fn store_spawn_point(
+    mut commands: Commands,
+    players: Query<(Entity, &Transform, &Wallet), Added<Player>>,
+) {
+    for (player, transform, wallet) in &players {
+        commands.entity(player).insert(SpawnPoint {
+            transform: *transform,
+            wallet: wallet.clone(),
+        });
+    }
+}
+
+// Only respawns the selected level, its neighbours stay as they are.
+// Coins collected before the last checkpoint remove themselves as they respawn.
 fn respawn_level(
     mut commands: Commands,
     mut restart_level_events: EventReader<RestartLevelEvent>,
     level_selection: Res<LevelSelection>,
     levels: Query<(Entity, &LevelIid)>,
+    ldtk_projects: Query<&Handle<LdtkProject>>,
+    ldtk_project_assets: Res<Assets<LdtkProject>>,
 ) {
-    for _ in restart_level_events.read() {
-        let level_selection_iid = match level_selection.as_ref() {
-            LevelSelection::Iid(iid) => iid,
-            _ => panic!("level should always be selected by iid in this example"),
-        };
-
-        for (level_entity, level_iid) in levels.iter() {
-            if level_iid == level_selection_iid {
-                commands.entity(level_entity).insert(Respawn);
-            }
+    let restart = restart_level_events
+        .read()
+        .any(|event| event.scope != RestartScope::World);
+    if !restart {
+        return;
+    }
+
+    let Some(selected_level) = ldtk_projects
+        .get_single()
+        .ok()
+        .and_then(|handle| ldtk_project_assets.get(handle))
+        .and_then(|ldtk_project| ldtk_project.find_raw_level_by_level_selection(&level_selection))
+    else {
+        warn!("no level matches {:?}, nothing to restart", *level_selection);
+        return;
+    };
+
+    for (level_entity, level_iid) in levels.iter() {
+        if *level_iid.get() == selected_level.iid {
+            commands.entity(level_entity).insert(Respawn);
         }
     }
 }
@@ -35,10 +77,80 @@ fn respawn_world(
     mut commands: Commands,
     ldtk_projects: Query<Entity, With<Handle<LdtkProject>>>,
 ) {
-    for _ in restart_level_events.read() { 
-        commands.entity(ldtk_projects.single()).insert(Respawn);
+    let restart = restart_level_events
+        .read()
+        .any(|event| event.scope == RestartScope::World);
+
+    if restart {
+        for ldtk_project in &ldtk_projects {
+            commands.entity(ldtk_project).insert(Respawn);
+        }
     }
 }
 
+// The player is worldly, so respawning a level leaves them where they are.
+// Put them back at their spawn point or last checkpoint for every scope that
+// doesn't reload the world.
+#[allow(clippy::type_complexity)]
+fn respawn_player(
+    mut commands: Commands,
+    mut restart_level_events: EventReader<RestartLevelEvent>,
+    mut respawn_events: EventWriter<PlayerRespawned>,
+    mut players: Query<(
+        Entity,
+        &SpawnPoint,
+        Option<&LastCheckpoint>,
+        &JumpSettings,
+        &mut Transform,
+        &mut Wallet,
+        &mut JumpState,
+        &mut MovementState,
+        &mut DashState,
+        &mut PlayerState,
+        &mut TextureAtlasSprite,
+    )>,
+) {
+    let Some(scope) = restart_level_events
+        .read()
+        .map(|event| event.scope)
+        .filter(|&scope| scope != RestartScope::World)
+        .last()
+    else {
+        return;
+    };
 
+    for (
+        player,
+        spawn_point,
+        last_checkpoint,
+        settings,
+        mut transform,
+        mut wallet,
+        mut jump,
+        mut movement,
+        mut dash,
+        mut state,
+        mut sprite,
+    ) in &mut players
+    {
+        match last_checkpoint {
+            Some(last_checkpoint) if scope == RestartScope::Checkpoint => {
+                *transform = last_checkpoint.transform;
+                *wallet = last_checkpoint.wallet.clone();
+            }
+            _ => {
+                *transform = spawn_point.transform;
+                *wallet = spawn_point.wallet.clone();
+                commands.entity(player).remove::<LastCheckpoint>();
+            }
+        }
 
+        *jump = JumpState::default();
+        *movement = MovementState::default();
+        *dash = DashState::default();
+        *state = PlayerState::from(*settings);
+        sprite.color = Color::WHITE;
+        commands.entity(player).remove::<Dying>();
+        respawn_events.send_default();
+    }
+}
@@ -0,0 +1,105 @@
+/*
+    Seeded randomness for everything gameplay related.
+
+    Every consumer forks its own stream from the game seed by name, so adding
+    a new consumer never changes the numbers an existing one sees. Pass
+    `--seed <u64>` on the command line to replay a run.
+*/
+
+use bevy::prelude::*;
+use rand::SeedableRng;
+use rand_chacha::ChaCha8Rng;
+
+pub struct RngPlugin {
+    pub seed: u64,
+}
+
+impl Plugin for RngPlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(GameRng::new(self.seed))
+            .add_systems(Startup, log_seed);
+    }
+}
+
+#[derive(Resource, Clone, Copy, Debug)]
+pub struct GameRng {
+    seed: u64,
+}
+
+impl GameRng {
+    pub fn new(seed: u64) -> Self {
+        Self { seed }
+    }
+
+    pub fn seed(&self) -> u64 {
+        self.seed
+    }
+
+    /// Returns the stream for the subsystem called `name`.
+    ///
+    /// Forking the same name twice gives the same numbers, which is how
+    /// subsystems rewind their randomness when a level restarts.
+    pub fn fork(&self, name: &str) -> ChaCha8Rng {
+        let mut rng = ChaCha8Rng::seed_from_u64(self.seed);
+        rng.set_stream(stream_id(name));
+        rng
+    }
+}
+
+// FNV-1a, std's hashers are not guaranteed to be stable between releases.
+fn stream_id(name: &str) -> u64 {
+    name.bytes().fold(0xcbf29ce484222325, |hash, byte| {
+        (hash ^ byte as u64).wrapping_mul(0x100000001b3)
+    })
+}
+
+fn log_seed(rng: Res<GameRng>) {
+    info!("game seed: {} (replay with --seed {})", rng.seed(), rng.seed());
+}
+
+/// Reads `--seed <u64>` from the command line.
+pub fn seed_from_args() -> Option<u64> {
+    std::env::args()
+        .skip_while(|arg| arg != "--seed")
+        .nth(1)
+        .and_then(|seed| seed.parse().ok())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::Rng;
+
+    fn sample(rng: &mut ChaCha8Rng) -> Vec<u64> {
+        (0..8).map(|_| rng.gen()).collect()
+    }
+
+    #[test]
+    fn forking_the_same_name_repeats_the_stream() {
+        let game_rng = GameRng::new(42);
+
+        assert_eq!(
+            sample(&mut game_rng.fork("endless")),
+            sample(&mut game_rng.fork("endless"))
+        );
+        assert_eq!(
+            sample(&mut game_rng.fork("endless")),
+            sample(&mut GameRng::new(42).fork("endless"))
+        );
+    }
+
+    #[test]
+    fn named_forks_are_independent() {
+        let game_rng = GameRng::new(42);
+        let endless = sample(&mut game_rng.fork("endless"));
+
+        assert_ne!(endless, sample(&mut game_rng.fork("platforms")));
+        assert_ne!(endless, sample(&mut GameRng::new(43).fork("endless")));
+
+        // drawing from another fork doesn't move this one along
+        let mut other = game_rng.fork("platforms");
+        let mut after_other = game_rng.fork("endless");
+        sample(&mut other);
+        assert_eq!(endless, sample(&mut after_other));
+    }
+}
@@ -0,0 +1,387 @@
+/*
+    Campaign progress and settings that persist between runs.
+
+    Everything lives in `save.ron` in the platform data directory and is
+    written whenever it changes. The file starts with a version number. Fields
+    added since a save was written take their defaults, and a file that can't
+    be read at all is moved aside to `save.corrupt` so the game can start over
+    with defaults instead of refusing to run. A save with a version other than
+    this game's is left alone, and nothing is saved for the rest of the
+    session.
+*/
+
+use crate::actions::PlatformerAction;
+use crate::coin::Wallet;
+use crate::events::LevelCompleted;
+use crate::player_components::Player;
+use crate::respawn::SpawnPoint;
+use crate::score::RunStats;
+use crate::storage;
+use bevy::prelude::*;
+use bevy::window::{PrimaryWindow, WindowMode};
+use bevy_ecs_ldtk::prelude::*;
+use leafwing_input_manager::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use std::collections::{BTreeMap, BTreeSet};
+use std::path::{Path, PathBuf};
+
+pub struct SavePlugin {
+    /// Where the save is kept, `None` keeps it in memory only.
+    pub path: Option<PathBuf>,
+}
+
+impl Default for SavePlugin {
+    fn default() -> Self {
+        Self {
+            path: storage::data_path(SAVE_FILE),
+        }
+    }
+}
+
+impl Plugin for SavePlugin {
+    fn build(&self, app: &mut App) {
+        let mut path = self.path.clone();
+        let save_data = match path.as_deref().map(SaveData::load) {
+            None => SaveData::default(),
+            Some(Ok(save_data)) => save_data,
+            Some(Err(error)) => {
+                // play on in memory rather than overwrite a save this version can't read
+                warn!("{error}, progress won't be saved");
+                path = None;
+                SaveData::default()
+            }
+        };
+
+        app.insert_resource(SaveFile { path })
+            .insert_resource(save_data)
+            .add_systems(
+                Update,
+                (
+                    unlock_selected_level.run_if(resource_changed::<LevelSelection>()),
+                    record_level,
+                    apply_settings.run_if(resource_changed::<SaveData>()),
+                    apply_key_bindings,
+                    write_save.run_if(
+                        resource_changed::<SaveData>().and_then(not(resource_added::<SaveData>())),
+                    ),
+                )
+                    .chain(),
+            );
+    }
+}
+
+const SAVE_VERSION: u32 = 1;
+const SAVE_FILE: &str = "save.ron";
+
+#[derive(Resource, Clone, Debug)]
+pub struct SaveFile {
+    pub path: Option<PathBuf>,
+}
+
+#[derive(Resource, Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
+#[serde(default)]
+pub struct SaveData {
+    /// Iids of every level the player has reached.
+    pub unlocked_levels: BTreeSet<String>,
+    /// Fastest completion of each level in seconds, by level iid.
+    pub best_times: BTreeMap<String, f32>,
+    /// Coins collected in every level ever completed.
+    pub total_coins: u32,
+    pub settings: Settings,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(default)]
+pub struct Settings {
+    /// Master volume, from 0 to 1.
+    pub volume: f32,
+    pub window_mode: WindowMode,
+    /// Replaces the default controls when set.
+    pub key_bindings: Option<InputMap<PlatformerAction>>,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Self {
+            volume: 1.,
+            window_mode: WindowMode::Windowed,
+            key_bindings: None,
+        }
+    }
+}
+
+/// Just enough of a save file to tell which version wrote it.
+#[derive(Deserialize)]
+struct SaveHeader {
+    version: u32,
+}
+
+#[derive(Debug)]
+pub enum SaveError {
+    /// The save can't be read, it is moved aside when loading.
+    Corrupt(String),
+    /// The save was written by another version of the game, it is left alone.
+    Unsupported(u32),
+}
+
+impl std::fmt::Display for SaveError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            SaveError::Corrupt(error) => write!(f, "{error}"),
+            SaveError::Unsupported(version) => write!(
+                f,
+                "save version {version} isn't supported, this game reads version {SAVE_VERSION}"
+            ),
+        }
+    }
+}
+
+/// The save as it is stored on disk.
+#[derive(Serialize, Deserialize)]
+struct SaveFileContents {
+    version: u32,
+    data: SaveData,
+}
+
+impl SaveData {
+    /// Reads the save at `path`, falling back to defaults if there is none or
+    /// it can't be read. Only a save from another version is an error.
+    pub fn load(path: &Path) -> Result<Self, SaveError> {
+        let Ok(contents) = std::fs::read_to_string(path) else {
+            return Ok(Self::default());
+        };
+
+        match Self::parse(&contents) {
+            Ok(save_data) => Ok(save_data),
+            Err(error @ SaveError::Unsupported(_)) => Err(error),
+            Err(SaveError::Corrupt(error)) => {
+                let backup = path.with_extension("corrupt");
+                warn!(
+                    "couldn't read {}: {error}, moving it to {} and starting over",
+                    path.display(),
+                    backup.display()
+                );
+                if let Err(error) = std::fs::rename(path, &backup) {
+                    warn!("couldn't move {}: {error}", path.display());
+                }
+                Ok(Self::default())
+            }
+        }
+    }
+
+    /// Reads a save written with `SAVE_VERSION`.
+    pub fn parse(contents: &str) -> Result<Self, SaveError> {
+        let corrupt = |error: ron::error::SpannedError| SaveError::Corrupt(error.to_string());
+        let SaveHeader { version } = ron::from_str(contents).map_err(corrupt)?;
+
+        match version {
+            SAVE_VERSION => ron::from_str::<SaveFileContents>(contents)
+                .map(|file| file.data)
+                .map_err(corrupt),
+            version => Err(SaveError::Unsupported(version)),
+        }
+    }
+
+    pub fn to_ron(&self) -> Result<String, String> {
+        let file = SaveFileContents {
+            version: SAVE_VERSION,
+            data: self.clone(),
+        };
+        ron::ser::to_string_pretty(&file, ron::ser::PrettyConfig::default())
+            .map_err(|error| error.to_string())
+    }
+
+    pub fn save(&self, path: &Path) -> Result<(), String> {
+        storage::write_atomic(path, &self.to_ron()?).map_err(|error| error.to_string())
+    }
+}
+
+fn unlock_selected_level(mut save_data: ResMut<SaveData>, level_selection: Res<LevelSelection>) {
+    let LevelSelection::Iid(level_iid) = level_selection.as_ref() else {
+        return;
+    };
+
+    let level_iid = level_iid.to_string();
+    if !save_data.unlocked_levels.contains(&level_iid) {
+        save_data.unlocked_levels.insert(level_iid);
+    }
+}
+
+fn record_level(
+    mut save_data: ResMut<SaveData>,
+    mut level_completed_events: EventReader<LevelCompleted>,
+    stats: Res<RunStats>,
+    level_selection: Res<LevelSelection>,
+    players: Query<(&Wallet, &SpawnPoint), With<Player>>,
+) {
+    if level_completed_events.read().count() == 0 {
+        return;
+    }
+    let LevelSelection::Iid(level_iid) = level_selection.as_ref() else {
+        return;
+    };
+
+    let time = stats.level_time.elapsed_secs();
+    let best_time = save_data
+        .best_times
+        .entry(level_iid.to_string())
+        .or_insert(time);
+    *best_time = best_time.min(time);
+
+    // only what was picked up in this level, the rest was counted in earlier ones
+    for (wallet, spawn_point) in &players {
+        save_data.total_coins += wallet.coins().saturating_sub(spawn_point.wallet.coins());
+    }
+}
+
+fn apply_settings(
+    save_data: Res<SaveData>,
+    mut global_volume: ResMut<GlobalVolume>,
+    mut windows: Query<&mut Window, With<PrimaryWindow>>,
+    mut players: Query<&mut InputMap<PlatformerAction>, With<Player>>,
+) {
+    let settings = &save_data.settings;
+
+    *global_volume = GlobalVolume::new(settings.volume);
+    for mut window in &mut windows {
+        if window.mode != settings.window_mode {
+            window.mode = settings.window_mode;
+        }
+    }
+    if let Some(key_bindings) = &settings.key_bindings {
+        for mut input_map in &mut players {
+            *input_map = key_bindings.clone();
+        }
+    }
+}
+
+fn apply_key_bindings(
+    save_data: Res<SaveData>,
+    mut players: Query<&mut InputMap<PlatformerAction>, Added<Player>>,
+) {
+    let Some(key_bindings) = &save_data.settings.key_bindings else {
+        return;
+    };
+    for mut input_map in &mut players {
+        *input_map = key_bindings.clone();
+    }
+}
+
+fn write_save(save_data: Res<SaveData>, save_file: Res<SaveFile>) {
+    let Some(path) = &save_file.path else {
+        return;
+    };
+    if let Err(error) = save_data.save(path) {
+        warn!("couldn't write {}: {error}", path.display());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::events::EventPipelinePlugin;
+
+    fn temporary_path(name: &str) -> PathBuf {
+        std::env::temp_dir().join(format!(
+            "{}-{}-{name}.ron",
+            env!("CARGO_PKG_NAME"),
+            std::process::id()
+        ))
+    }
+
+    fn headless_app(path: &Path) -> App {
+        let mut app = App::new();
+        app.add_plugins(MinimalPlugins)
+            .add_plugins(EventPipelinePlugin)
+            .init_resource::<GlobalVolume>()
+            .init_resource::<RunStats>()
+            .insert_resource(LevelSelection::index(0))
+            .add_plugins(SavePlugin {
+                path: Some(path.to_path_buf()),
+            });
+        app
+    }
+
+    fn example_save() -> SaveData {
+        let mut key_bindings = InputMap::default();
+        key_bindings.insert(KeyCode::Up, PlatformerAction::Jump);
+
+        SaveData {
+            unlocked_levels: ["first", "second"].map(String::from).into(),
+            best_times: [("first".to_string(), 12.5)].into(),
+            total_coins: 7,
+            settings: Settings {
+                volume: 0.25,
+                window_mode: WindowMode::BorderlessFullscreen,
+                key_bindings: Some(key_bindings),
+            },
+        }
+    }
+
+    #[test]
+    fn round_trips_through_the_save_file() {
+        let path = temporary_path("round-trip");
+        let _ = std::fs::remove_file(&path);
+
+        let mut app = headless_app(&path);
+        app.update();
+        assert!(!path.exists(), "an untouched save shouldn't be written");
+
+        *app.world.resource_mut::<SaveData>() = example_save();
+        app.update();
+        assert_eq!(app.world.resource::<GlobalVolume>().volume.get(), 0.25);
+
+        let reloaded = headless_app(&path);
+        assert_eq!(*reloaded.world.resource::<SaveData>(), example_save());
+
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn fills_in_fields_missing_from_older_saves() {
+        let save_data = SaveData::parse("(version: 1, data: (total_coins: 3))").unwrap();
+
+        assert_eq!(save_data.total_coins, 3);
+        assert_eq!(save_data.settings, Settings::default());
+    }
+
+    #[test]
+    fn treats_a_save_without_a_version_as_corrupt() {
+        assert!(matches!(
+            SaveData::parse("(data: (total_coins: 3))"),
+            Err(SaveError::Corrupt(_))
+        ));
+    }
+
+    #[test]
+    fn recovers_from_a_corrupt_save() {
+        let path = temporary_path("corrupt");
+        let backup = path.with_extension("corrupt");
+        std::fs::write(&path, "(version: 1, data: (total_coins: \"lots\"))").unwrap();
+
+        let app = headless_app(&path);
+        assert_eq!(*app.world.resource::<SaveData>(), SaveData::default());
+        assert!(!path.exists());
+        assert!(backup.exists());
+
+        std::fs::remove_file(&backup).unwrap();
+    }
+
+    #[test]
+    fn leaves_saves_from_other_versions_alone() {
+        for version in [SAVE_VERSION - 1, SAVE_VERSION + 1] {
+            let path = temporary_path(&format!("version-{version}"));
+            let contents = format!("(version: {version}, data: ())");
+            std::fs::write(&path, &contents).unwrap();
+
+            let mut app = headless_app(&path);
+            app.world.resource_mut::<SaveData>().total_coins = 3;
+            app.update();
+            assert_eq!(std::fs::read_to_string(&path).unwrap(), contents);
+            assert!(!path.with_extension("corrupt").exists());
+
+            std::fs::remove_file(&path).unwrap();
+        }
+    }
+}
@@ -0,0 +1,153 @@
+/*
+    Keeps track of how well the current run is going.
+
+    Height is measured in meters above the spawn point of the level, the
+    score rewards the best height reached in every level of the run, every
+    coin collected and every enemy stomped. Changes are announced as events
+    for the HUD and anything else interested. A run starts when the player
+    starts playing from the title screen.
+*/
+
+use crate::events::{CoinsChanged, EnemyStomped, HeightChanged, LevelTimeChanged, ScoreChanged};
+use crate::game_state::GameState;
+use crate::player_components::Player;
+use crate::respawn::SpawnPoint;
+use bevy::prelude::*;
+use bevy::time::Stopwatch;
+
+pub struct ScorePlugin;
+
+impl Plugin for ScorePlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<RunStats>()
+            .add_systems(
+                Update,
+                (
+                    reset_level,
+                    tick_level_time.run_if(in_state(GameState::Playing)),
+                    track_height,
+                    count_coins,
+                    count_stomps,
+                    update_score,
+                )
+                    .chain(),
+            )
+            .add_systems(
+                OnTransition {
+                    from: GameState::Title,
+                    to: GameState::Playing,
+                },
+                reset_run,
+            );
+    }
+}
+
+/// Pixels per meter of height.
+const PIXELS_PER_METER: f32 = 16.;
+const POINTS_PER_METER: u32 = 10;
+const POINTS_PER_COIN: u32 = 50;
+const POINTS_PER_STOMP: u32 = 100;
+
+#[derive(Resource, Default, Debug)]
+pub struct RunStats {
+    pub coins: u32,
+    pub stomps: u32,
+    /// Current height in the level being played.
+    pub height: u32,
+    /// Highest point reached in the level being played.
+    pub best_height: u32,
+    /// Sum of the best heights of the levels already left behind.
+    pub banked_height: u32,
+    pub level_time: Stopwatch,
+}
+
+impl RunStats {
+    pub fn score(&self) -> u32 {
+        (self.banked_height + self.best_height) * POINTS_PER_METER
+            + self.coins * POINTS_PER_COIN
+            + self.stomps * POINTS_PER_STOMP
+    }
+}
+
+fn reset_run(mut stats: ResMut<RunStats>) {
+    *stats = RunStats::default();
+}
+
+// A new player means a new level, the height climbed in the last one still counts.
+fn reset_level(
+    mut stats: ResMut<RunStats>,
+    mut height_changed: EventWriter<HeightChanged>,
+    mut level_time_changed: EventWriter<LevelTimeChanged>,
+    players: Query<(), Added<Player>>,
+) {
+    if players.is_empty() {
+        return;
+    }
+
+    stats.banked_height += stats.best_height;
+    stats.height = 0;
+    stats.best_height = 0;
+    stats.level_time.reset();
+    height_changed.send(HeightChanged { height: 0, best: 0 });
+    level_time_changed.send(LevelTimeChanged { seconds: 0 });
+}
+
+fn tick_level_time(
+    mut stats: ResMut<RunStats>,
+    mut level_time_changed: EventWriter<LevelTimeChanged>,
+    time: Res<Time>,
+) {
+    let before = stats.level_time.elapsed().as_secs();
+    let after = stats.level_time.tick(time.delta()).elapsed().as_secs();
+
+    if after != before {
+        level_time_changed.send(LevelTimeChanged {
+            seconds: after as u32,
+        });
+    }
+}
+
+fn track_height(
+    mut stats: ResMut<RunStats>,
+    mut height_changed: EventWriter<HeightChanged>,
+    players: Query<(&Transform, &SpawnPoint), With<Player>>,
+) {
+    let Ok((transform, spawn_point)) = players.get_single() else {
+        return;
+    };
+
+    let climbed = transform.translation.y - spawn_point.transform.translation.y;
+    let height = (climbed / PIXELS_PER_METER).max(0.) as u32;
+    if height == stats.height {
+        return;
+    }
+
+    stats.height = height;
+    stats.best_height = stats.best_height.max(height);
+    height_changed.send(HeightChanged {
+        height,
+        best: stats.best_height,
+    });
+}
+
+fn count_coins(mut stats: ResMut<RunStats>, mut coins_changed: EventReader<CoinsChanged>) {
+    if let Some(coins) = coins_changed.read().last() {
+        stats.coins = coins.total;
+    }
+}
+
+fn count_stomps(mut stats: ResMut<RunStats>, mut stomps: EventReader<EnemyStomped>) {
+    stats.stomps += stomps.read().count() as u32;
+}
+
+fn update_score(
+    stats: Res<RunStats>,
+    mut score_changed: EventWriter<ScoreChanged>,
+    mut last_score: Local<Option<u32>>,
+) {
+    let score = stats.score();
+    if *last_score != Some(score) {
+        *last_score = Some(score);
+        score_changed.send(ScoreChanged { score });
+    }
+}
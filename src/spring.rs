@@ -0,0 +1,137 @@
+use crate::components::{ColliderBundle, GroundSensor};
+use crate::game_state::GameState;
+use crate::player::{Event, PlayerState};
+use crate::player_components::{float_field, JumpSettings, JumpState};
+use bevy::prelude::*;
+use bevy_easings::*;
+use bevy_ecs_ldtk::prelude::*;
+use bevy_rapier2d::prelude::*;
+
+use std::time::Duration;
+
+pub struct SpringPlugin;
+
+impl Plugin for SpringPlugin {
+    fn build(&self, app: &mut App) {
+        app.register_ldtk_entity::<SpringBundle>("Spring")
+            .add_systems(
+                Update,
+                (
+                    store_rest_transform,
+                    launch.run_if(in_state(GameState::Playing)),
+                )
+                    .chain(),
+            );
+    }
+}
+
+/// Default `launch_strength` when the LDtk field is missing.
+const DEFAULT_LAUNCH_STRENGTH: f32 = 2.5;
+/// How squashed the spring gets at the bottom of its compression.
+const COMPRESSED_SCALE: f32 = 0.5;
+
+#[derive(Clone, Copy, Debug, Component)]
+pub struct Spring {
+    /// Launch velocity as a multiple of the player's normal jump velocity.
+    pub launch_strength: f32,
+    rest: Transform,
+}
+
+impl Default for Spring {
+    fn default() -> Self {
+        Spring {
+            launch_strength: DEFAULT_LAUNCH_STRENGTH,
+            rest: Transform::default(),
+        }
+    }
+}
+
+impl From<&EntityInstance> for Spring {
+    fn from(entity_instance: &EntityInstance) -> Self {
+        Spring {
+            launch_strength: float_field(
+                entity_instance,
+                "launch_strength",
+                DEFAULT_LAUNCH_STRENGTH,
+            ),
+            ..Default::default()
+        }
+    }
+}
+
+#[derive(Clone, Default, Bundle, LdtkEntity)]
+pub struct SpringBundle {
+    #[sprite_sheet_bundle]
+    sprite_sheet_bundle: SpriteSheetBundle,
+    #[from_entity_instance]
+    pub collider_bundle: ColliderBundle,
+    #[from_entity_instance]
+    pub spring: Spring,
+}
+
+// LDtk positions the spring after the bundle is built, so remember where it sits
+// to always animate back to the same place.
+fn store_rest_transform(mut springs: Query<(&mut Spring, &Transform), Added<Spring>>) {
+    for (mut spring, transform) in &mut springs {
+        spring.rest = *transform;
+    }
+}
+
+fn launch(
+    mut commands: Commands,
+    ground_sensors: Query<&GroundSensor, Changed<GroundSensor>>,
+    springs: Query<(Entity, &Spring, &Collider)>,
+    mut players: Query<(&JumpSettings, &mut JumpState, &mut PlayerState)>,
+    time: Res<Time>,
+) {
+    for sensor in &ground_sensors {
+        let Ok((settings, mut jump, mut state)) = players.get_mut(sensor.ground_detection_entity)
+        else {
+            continue;
+        };
+
+        // only bounce when landing on the spring, not when rising past it
+        if jump.velocity > 0. {
+            continue;
+        }
+
+        let Some((spring_entity, spring, collider)) = sensor
+            .intersecting_ground_entities
+            .iter()
+            .find_map(|&ground| springs.get(ground).ok())
+        else {
+            continue;
+        };
+
+        state.handle(&Event::Launch {
+            event_time: time.elapsed(),
+        });
+        jump.time.reset();
+        jump.velocity = settings.jump_velocity() * spring.launch_strength;
+        jump.rising = true;
+        jump.peak_reached = false;
+
+        let rest = spring.rest;
+        let mut compressed = rest.with_scale(rest.scale * Vec3::new(1., COMPRESSED_SCALE, 1.));
+        // keep the bottom of the spring in place while it squashes
+        let half_height = collider.as_cuboid().map_or(0., |cuboid| cuboid.half_extents().y);
+        compressed.translation.y -= half_height * (1. - COMPRESSED_SCALE);
+
+        commands.entity(spring_entity).insert(
+            rest.ease_to(
+                compressed,
+                EaseFunction::QuadraticOut,
+                EasingType::Once {
+                    duration: Duration::from_millis(60),
+                },
+            )
+            .ease_to(
+                rest,
+                EaseFunction::ElasticOut,
+                EasingType::Once {
+                    duration: Duration::from_millis(400),
+                },
+            ),
+        );
+    }
+}
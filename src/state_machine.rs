@@ -1,7 +1,3 @@
-use bevy::prelude::*;
-use crate::actions::PlatformerAction;
-use leafwing_input_manager::prelude::*;
-
+#[allow(dead_code)]
 pub struct PlayerStateMachinePlugin;
 
-
@@ -0,0 +1,21 @@
+/*
+    Files the game keeps between runs, all in the platform data directory.
+*/
+
+use std::path::{Path, PathBuf};
+
+/// Where `file` lives in the game's data directory, if the platform has one.
+pub fn data_path(file: &str) -> Option<PathBuf> {
+    dirs::data_dir().map(|dir| dir.join(env!("CARGO_PKG_NAME")).join(file))
+}
+
+/// Replaces the file at `path`, writing next to it first and swapping it in so
+/// a crash never leaves half a file behind.
+pub fn write_atomic(path: &Path, contents: &str) -> std::io::Result<()> {
+    if let Some(dir) = path.parent() {
+        std::fs::create_dir_all(dir)?;
+    }
+    let temporary = path.with_extension("tmp");
+    std::fs::write(&temporary, contents)?;
+    std::fs::rename(&temporary, path)
+}
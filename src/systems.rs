@@ -1,15 +1,11 @@
 use crate::components::*;
+use crate::wrap::{level_wraps, seam_rects};
 use bevy::prelude::*;
 use bevy_ecs_ldtk::prelude::*;
 use bevy_rapier2d::prelude::*;
 
 use std::collections::{HashMap, HashSet};
 
-pub fn setup_camera(mut commands: Commands) {
-    let camera = Camera2dBundle::default();
-    commands.spawn(camera);
-}
-
 pub fn setup_ldtk_world(mut commands: Commands, asset_server: Res<AssetServer>) {
     let ldtk_handle = asset_server.load("tile-based-game.ldtk");
     commands.spawn(LdtkWorldBundle {
@@ -18,7 +14,121 @@ pub fn setup_ldtk_world(mut commands: Commands, asset_server: Res<AssetServer>)
     });
 }
 
-/// Spawns rapier collisions for the walls of a level
+/// A simple rectangle type representing a wall of any size, in grid coordinates
+#[derive(Clone, Copy, Eq, PartialEq, Debug)]
+pub struct WallRect {
+    pub left: i32,
+    pub right: i32,
+    pub top: i32,
+    pub bottom: i32,
+}
+
+/// Combines wall tiles into a small number of rectangles
+///
+/// The algorithm used here is a nice compromise between simplicity, speed,
+/// and a small number of rectangle colliders.
+/// In basic terms, it will:
+/// 1. combine wall tiles into flat "plates" in each individual row
+/// 2. combine the plates into rectangles across multiple rows wherever possible
+pub fn merge_wall_rects(walls: &HashSet<GridCoords>, width: i32, height: i32) -> Vec<WallRect> {
+    /// Represents a wide wall that is 1 tile tall
+    /// Used to spawn wall collisions
+    #[derive(Clone, Eq, PartialEq, Debug, Default, Hash)]
+    struct Plate {
+        left: i32,
+        right: i32,
+    }
+
+    // combine wall tiles into flat "plates" in each individual row
+    let mut plate_stack: Vec<Vec<Plate>> = Vec::new();
+
+    for y in 0..height {
+        let mut row_plates: Vec<Plate> = Vec::new();
+        let mut plate_start = None;
+
+        // + 1 to the width so the algorithm "terminates" plates that touch the right edge
+        for x in 0..width + 1 {
+            match (plate_start, walls.contains(&GridCoords { x, y })) {
+                (Some(s), false) => {
+                    row_plates.push(Plate {
+                        left: s,
+                        right: x - 1,
+                    });
+                    plate_start = None;
+                }
+                (None, true) => plate_start = Some(x),
+                _ => (),
+            }
+        }
+
+        plate_stack.push(row_plates);
+    }
+
+    // combine "plates" into rectangles across multiple rows
+    let mut rect_builder: HashMap<Plate, WallRect> = HashMap::new();
+    let mut prev_row: Vec<Plate> = Vec::new();
+    let mut wall_rects: Vec<WallRect> = Vec::new();
+
+    // an extra empty row so the algorithm "finishes" the rects that touch the top edge
+    plate_stack.push(Vec::new());
+
+    for (y, current_row) in plate_stack.into_iter().enumerate() {
+        for prev_plate in &prev_row {
+            if !current_row.contains(prev_plate) {
+                // remove the finished rect so that the same plate in the future starts a new rect
+                if let Some(rect) = rect_builder.remove(prev_plate) {
+                    wall_rects.push(rect);
+                }
+            }
+        }
+        for plate in &current_row {
+            rect_builder
+                .entry(plate.clone())
+                .and_modify(|e| e.top += 1)
+                .or_insert(WallRect {
+                    bottom: y as i32,
+                    top: y as i32,
+                    left: plate.left,
+                    right: plate.right,
+                });
+        }
+        prev_row = current_row;
+    }
+
+    wall_rects
+}
+
+/// Spawns a fixed collider for every rectangle as a child of `parent`,
+/// tagged with `T` so other systems can tell what kind of wall it is
+///
+/// Making the collider a child of the level serves two purposes:
+/// 1. Adjusts the transforms to be relative to the level for free
+/// 2. the colliders will be despawned automatically when levels unload
+pub fn spawn_wall_rects<T: Component + Default>(
+    parent: &mut ChildBuilder,
+    wall_rects: &[WallRect],
+    grid_size: i32,
+) {
+    for wall_rect in wall_rects {
+        parent
+            .spawn(T::default())
+            .insert(Collider::cuboid(
+                (wall_rect.right as f32 - wall_rect.left as f32 + 1.) * grid_size as f32 / 2.,
+                (wall_rect.top as f32 - wall_rect.bottom as f32 + 1.) * grid_size as f32 / 2.,
+            ))
+            .insert(RigidBody::Fixed)
+            .insert(Friction::new(1.0))
+            .insert(Transform::from_xyz(
+                (wall_rect.left + wall_rect.right + 1) as f32 * grid_size as f32 / 2.,
+                (wall_rect.bottom + wall_rect.top + 1) as f32 * grid_size as f32 / 2.,
+                0.,
+            ))
+            .insert(GlobalTransform::default());
+    }
+}
+
+/// Spawns rapier collisions for the walls of a level, for every IntGrid marker `T`
+/// (solid [`Wall`]s, [`OneWayPlatform`]s)
 ///
 /// You could just insert a ColliderBundle in to the WallBundle,
 /// but this spawns a different collider for EVERY wall tile.
@@ -27,37 +137,18 @@ pub fn setup_ldtk_world(mut commands: Commands, asset_server: Res<AssetServer>)
 /// Instead, by flagging the wall tiles and spawning the collisions later,
 /// we can minimize the amount of colliding entities.
 ///
-/// The algorithm used here is a nice compromise between simplicity, speed,
-/// and a small number of rectangle colliders.
 /// In basic terms, it will:
 /// 1. consider where the walls are
-/// 2. combine wall tiles into flat "plates" in each individual row
-/// 3. combine the plates into rectangles across multiple rows wherever possible
-/// 4. spawn colliders for each rectangle
-pub fn spawn_wall_collision(
+/// 2. merge them into rectangles with [`merge_wall_rects`]
+/// 3. spawn colliders for each rectangle with [`spawn_wall_rects`]
+pub fn spawn_wall_collision<T: Component + Default>(
     mut commands: Commands,
-    wall_query: Query<(&GridCoords, &Parent), Added<Wall>>,
-    parent_query: Query<&Parent, Without<Wall>>,
+    wall_query: Query<(&GridCoords, &Parent), Added<T>>,
+    parent_query: Query<&Parent, Without<T>>,
     level_query: Query<(Entity, &LevelIid)>,
     ldtk_projects: Query<&Handle<LdtkProject>>,
     ldtk_project_assets: Res<Assets<LdtkProject>>,
 ) {
-    /// Represents a wide wall that is 1 tile tall
-    /// Used to spawn wall collisions
-    #[derive(Clone, Eq, PartialEq, Debug, Default, Hash)]
-    struct Plate {
-        left: i32,
-        right: i32,
-    }
-
-    /// A simple rectangle type representing a wall of any size
-    struct Rect {
-        left: i32,
-        right: i32,
-        top: i32,
-        bottom: i32,
-    }
-
     // Consider where the walls are
     // storing them as GridCoords in a HashSet for quick, easy lookup
     //
@@ -98,88 +189,14 @@ pub fn spawn_wall_collision(
                     ..
                 } = level.layer_instances()[0];
 
-                // combine wall tiles into flat "plates" in each individual row
-                let mut plate_stack: Vec<Vec<Plate>> = Vec::new();
-
-                for y in 0..height {
-                    let mut row_plates: Vec<Plate> = Vec::new();
-                    let mut plate_start = None;
-
-                    // + 1 to the width so the algorithm "terminates" plates that touch the right edge
-                    for x in 0..width + 1 {
-                        match (plate_start, level_walls.contains(&GridCoords { x, y })) {
-                            (Some(s), false) => {
-                                row_plates.push(Plate {
-                                    left: s,
-                                    right: x - 1,
-                                });
-                                plate_start = None;
-                            }
-                            (None, true) => plate_start = Some(x),
-                            _ => (),
-                        }
-                    }
-
-                    plate_stack.push(row_plates);
-                }
-
-                // combine "plates" into rectangles across multiple rows
-                let mut rect_builder: HashMap<Plate, Rect> = HashMap::new();
-                let mut prev_row: Vec<Plate> = Vec::new();
-                let mut wall_rects: Vec<Rect> = Vec::new();
-
-                // an extra empty row so the algorithm "finishes" the rects that touch the top edge
-                plate_stack.push(Vec::new());
-
-                for (y, current_row) in plate_stack.into_iter().enumerate() {
-                    for prev_plate in &prev_row {
-                        if !current_row.contains(prev_plate) {
-                            // remove the finished rect so that the same plate in the future starts a new rect
-                            if let Some(rect) = rect_builder.remove(prev_plate) {
-                                wall_rects.push(rect);
-                            }
-                        }
-                    }
-                    for plate in &current_row {
-                        rect_builder
-                            .entry(plate.clone())
-                            .and_modify(|e| e.top += 1)
-                            .or_insert(Rect {
-                                bottom: y as i32,
-                                top: y as i32,
-                                left: plate.left,
-                                right: plate.right,
-                            });
-                    }
-                    prev_row = current_row;
-                }
+                let wall_rects = merge_wall_rects(level_walls, width, height);
+                let wraps = level_wraps(&level);
 
                 commands.entity(level_entity).with_children(|level| {
-                    // Spawn colliders for every rectangle..
-                    // Making the collider a child of the level serves two purposes:
-                    // 1. Adjusts the transforms to be relative to the level for free
-                    // 2. the colliders will be despawned automatically when levels unload
-                    for wall_rect in wall_rects {
-                        level
-                            .spawn_empty()
-                            .insert(Collider::cuboid(
-                                (wall_rect.right as f32 - wall_rect.left as f32 + 1.)
-                                    * grid_size as f32
-                                    / 2.,
-                                (wall_rect.top as f32 - wall_rect.bottom as f32 + 1.)
-                                    * grid_size as f32
-                                    / 2.,
-                            ))
-                            .insert(RigidBody::Fixed)
-                            .insert(Friction::new(1.0))
-                            .insert(Transform::from_xyz(
-                                (wall_rect.left + wall_rect.right + 1) as f32 * grid_size as f32
-                                    / 2.,
-                                (wall_rect.bottom + wall_rect.top + 1) as f32 * grid_size as f32
-                                    / 2.,
-                                0.,
-                            ))
-                            .insert(GlobalTransform::default());
+                    spawn_wall_rects::<T>(level, &wall_rects, grid_size);
+
+                    if wraps {
+                        spawn_wall_rects::<T>(level, &seam_rects(&wall_rects, width), grid_size);
                     }
                 });
             }
@@ -207,6 +224,13 @@ pub fn spawn_ground_sensor(
                 builder
                     .spawn_empty()
                     .insert(ActiveEvents::COLLISION_EVENTS)
+                    // the player is kinematic and walls are fixed, a pair rapier ignores by default,
+                    // same for moving platforms which are kinematic as well
+                    .insert(
+                        ActiveCollisionTypes::default()
+                            | ActiveCollisionTypes::KINEMATIC_STATIC
+                            | ActiveCollisionTypes::KINEMATIC_KINEMATIC,
+                    )
                     .insert(detector_shape)
                     .insert(Sensor)
                     .insert(Transform::from_translation(sensor_translation))
@@ -265,3 +289,97 @@ pub fn update_on_ground(
         }
     }
 }
+
+pub fn spawn_wall_sensors(
+    mut commands: Commands,
+    detect_walls_for: Query<(Entity, &Collider), Added<WallDetection>>,
+) {
+    for (entity, shape) in &detect_walls_for {
+        if let Some(cuboid) = shape.as_cuboid() {
+            let Vec2 {
+                x: half_extents_x,
+                y: half_extents_y,
+            } = cuboid.half_extents();
+
+            let detector_shape = wall_sensor_shape(half_extents_y);
+
+            commands.entity(entity).with_children(|builder| {
+                for side in [-1., 1.] {
+                    builder
+                        .spawn_empty()
+                        .insert(ActiveEvents::COLLISION_EVENTS)
+                        // walls are fixed, see the ground sensor
+                        .insert(
+                            ActiveCollisionTypes::default()
+                                | ActiveCollisionTypes::KINEMATIC_STATIC,
+                        )
+                        .insert(detector_shape.clone())
+                        .insert(Sensor)
+                        .insert(Transform::from_xyz(side * half_extents_x, 0., 0.))
+                        .insert(GlobalTransform::default())
+                        .insert(WallSensor {
+                            wall_detection_entity: entity,
+                            side,
+                            intersecting_wall_entities: HashSet::new(),
+                        });
+                }
+            });
+        }
+    }
+}
+
+/// Wall sensor for a collider `half_height` tall, half as tall as the collider
+/// so floors and ceilings don't count as walls.
+pub fn wall_sensor_shape(half_height: f32) -> Collider {
+    Collider::cuboid(2., half_height / 2.0)
+}
+
+// Only solid walls can be slid down, not one-way or moving platforms.
+pub fn wall_detection(
+    mut wall_sensors: Query<&mut WallSensor>,
+    mut collisions: EventReader<CollisionEvent>,
+    walls: Query<With<Wall>, With<Collider>>,
+) {
+    for collision_event in collisions.read() {
+        match collision_event {
+            CollisionEvent::Started(e1, e2, _) => {
+                if walls.contains(*e1) {
+                    if let Ok(mut sensor) = wall_sensors.get_mut(*e2) {
+                        sensor.intersecting_wall_entities.insert(*e1);
+                    }
+                } else if walls.contains(*e2) {
+                    if let Ok(mut sensor) = wall_sensors.get_mut(*e1) {
+                        sensor.intersecting_wall_entities.insert(*e2);
+                    }
+                }
+            }
+            CollisionEvent::Stopped(e1, e2, _) => {
+                if walls.contains(*e1) {
+                    if let Ok(mut sensor) = wall_sensors.get_mut(*e2) {
+                        sensor.intersecting_wall_entities.remove(e1);
+                    }
+                } else if walls.contains(*e2) {
+                    if let Ok(mut sensor) = wall_sensors.get_mut(*e1) {
+                        sensor.intersecting_wall_entities.remove(e2);
+                    }
+                }
+            }
+        }
+    }
+}
+
+pub fn update_on_wall(
+    mut wall_detectors: Query<&mut WallDetection>,
+    wall_sensors: Query<&WallSensor, Changed<WallSensor>>,
+) {
+    for sensor in &wall_sensors {
+        if let Ok(mut wall_detection) = wall_detectors.get_mut(sensor.wall_detection_entity) {
+            let on_wall = !sensor.intersecting_wall_entities.is_empty();
+            if sensor.side < 0. {
+                wall_detection.on_wall_left = on_wall;
+            } else {
+                wall_detection.on_wall_right = on_wall;
+            }
+        }
+    }
+}
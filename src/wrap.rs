@@ -0,0 +1,277 @@
+/*
+    Doodle Jump style wrap-around on the horizontal edges of a level.
+
+    Opt in per level with a `wrap` bool field on the LDtk level. Entities with
+    `Wraps` are teleported across the level width when they leave it, walls
+    touching an edge get a copy of their collider on the other side of the
+    seam, and sprites get a ghost copy so they are drawn on both sides while
+    straddling it.
+*/
+
+use crate::game_state::GameState;
+use crate::player_components::Player;
+use crate::systems::WallRect;
+use bevy::prelude::*;
+use bevy_ecs_ldtk::prelude::*;
+
+pub struct WrapPlugin;
+
+impl Plugin for WrapPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<LevelWrap>().add_systems(
+            Update,
+            (
+                update_level_wrap,
+                add_player_wrap,
+                spawn_wrap_ghosts,
+                (wrap_entities, update_wrap_ghosts)
+                    .chain()
+                    .run_if(in_state(GameState::Playing)),
+            )
+                .chain(),
+        );
+    }
+}
+
+/// Horizontal extent of the selected level, if that level wraps.
+#[derive(Resource, Default, Clone, Copy, Debug)]
+pub struct LevelWrap(pub Option<WrapBounds>);
+
+#[derive(Clone, Copy, Debug)]
+pub struct WrapBounds {
+    pub left: f32,
+    pub width: f32,
+}
+
+/// Marks an entity that is teleported across the level when it leaves a wrapping level.
+#[derive(Clone, Copy, Default, Debug, Component)]
+pub struct Wraps;
+
+/// The copy of a wrapping entity's sprite drawn on the other side of the seam.
+#[derive(Component)]
+pub struct WrapGhost;
+
+/// Whether a level opted into wrap-around.
+pub fn level_wraps(level: &impl LdtkFields) -> bool {
+    level.get_bool_field("wrap").copied().unwrap_or(false)
+}
+
+/// Copies of the rectangles touching the left or right edge, moved across the seam.
+///
+/// Spawned next to the originals so anything straddling the seam collides with
+/// the walls on the far side as well.
+pub fn seam_rects(wall_rects: &[WallRect], width: i32) -> Vec<WallRect> {
+    let mut seam_rects = Vec::new();
+
+    for wall_rect in wall_rects {
+        if wall_rect.left == 0 {
+            seam_rects.push(WallRect {
+                left: wall_rect.left + width,
+                right: wall_rect.right + width,
+                ..*wall_rect
+            });
+        }
+        if wall_rect.right == width - 1 {
+            seam_rects.push(WallRect {
+                left: wall_rect.left - width,
+                right: wall_rect.right - width,
+                ..*wall_rect
+            });
+        }
+    }
+
+    seam_rects
+}
+
+fn update_level_wrap(
+    mut level_wrap: ResMut<LevelWrap>,
+    level_query: Query<(&Transform, &LevelIid)>,
+    ldtk_projects: Query<&Handle<LdtkProject>>,
+    ldtk_project_assets: Res<Assets<LdtkProject>>,
+    level_selection: Res<LevelSelection>,
+) {
+    let Some(ldtk_project) = ldtk_projects
+        .get_single()
+        .ok()
+        .and_then(|handle| ldtk_project_assets.get(handle))
+    else {
+        return;
+    };
+
+    let mut bounds = None;
+    for (level_transform, level_iid) in &level_query {
+        let Some(level) = ldtk_project.get_raw_level_by_iid(&level_iid.to_string()) else {
+            continue;
+        };
+
+        if level_selection.is_match(&LevelIndices::default(), level) && level_wraps(level) {
+            bounds = Some(WrapBounds {
+                left: level_transform.translation.x,
+                width: level.px_wid as f32,
+            });
+        }
+    }
+
+    level_wrap.0 = bounds;
+}
+
+fn add_player_wrap(mut commands: Commands, players: Query<Entity, Added<Player>>) {
+    for player in &players {
+        commands.entity(player).insert(Wraps);
+    }
+}
+
+pub fn spawn_wrap_ghosts(
+    mut commands: Commands,
+    wrapping: Query<(Entity, &Handle<TextureAtlas>, &TextureAtlasSprite), Added<Wraps>>,
+) {
+    for (entity, texture_atlas, sprite) in &wrapping {
+        commands.entity(entity).with_children(|parent| {
+            parent
+                .spawn(SpriteSheetBundle {
+                    texture_atlas: texture_atlas.clone(),
+                    sprite: sprite.clone(),
+                    visibility: Visibility::Hidden,
+                    ..Default::default()
+                })
+                .insert(WrapGhost);
+        });
+    }
+}
+
+fn wrap_entities(
+    level_wrap: Res<LevelWrap>,
+    mut wrapping: Query<(&mut Transform, &GlobalTransform), With<Wraps>>,
+) {
+    let Some(WrapBounds { left, width }) = level_wrap.0 else {
+        return;
+    };
+
+    for (mut transform, global_transform) in &mut wrapping {
+        let x = global_transform.translation().x;
+        if x < left {
+            transform.translation.x += width;
+        } else if x >= left + width {
+            transform.translation.x -= width;
+        }
+    }
+}
+
+#[allow(clippy::type_complexity)]
+fn update_wrap_ghosts(
+    level_wrap: Res<LevelWrap>,
+    wrapping: Query<
+        (
+            &GlobalTransform,
+            &Handle<TextureAtlas>,
+            &TextureAtlasSprite,
+            &Children,
+        ),
+        (With<Wraps>, Without<WrapGhost>),
+    >,
+    mut ghosts: Query<
+        (
+            &mut Transform,
+            &mut Handle<TextureAtlas>,
+            &mut TextureAtlasSprite,
+            &mut Visibility,
+        ),
+        With<WrapGhost>,
+    >,
+    texture_atlases: Res<Assets<TextureAtlas>>,
+) {
+    for (global_transform, texture_atlas, sprite, children) in &wrapping {
+        let half_width = sprite
+            .custom_size
+            .or_else(|| {
+                let atlas = texture_atlases.get(texture_atlas)?;
+                Some(atlas.textures.get(sprite.index)?.size())
+            })
+            .map_or(0., |size| size.x / 2.);
+
+        // the ghost sits a full level width away, on whichever side the sprite pokes out of
+        let x = global_transform.translation().x;
+        let offset = match level_wrap.0 {
+            Some(WrapBounds { left, width }) if x - half_width < left => width,
+            Some(WrapBounds { left, width }) if x + half_width > left + width => -width,
+            _ => 0.,
+        };
+
+        for &child in children {
+            if let Ok((mut ghost_transform, mut ghost_atlas, mut ghost_sprite, mut visibility)) =
+                ghosts.get_mut(child)
+            {
+                // the sprite's frames only make sense against the atlas they index into
+                if *ghost_atlas != *texture_atlas {
+                    *ghost_atlas = texture_atlas.clone();
+                }
+                *ghost_sprite = sprite.clone();
+                ghost_transform.translation.x = offset;
+                *visibility = if offset == 0. {
+                    Visibility::Hidden
+                } else {
+                    Visibility::Inherited
+                };
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::animation::AnimationPlugin;
+
+    fn ghost_atlas(app: &mut App) -> Handle<TextureAtlas> {
+        let mut ghosts = app
+            .world
+            .query_filtered::<&Handle<TextureAtlas>, With<WrapGhost>>();
+        ghosts.single(&app.world).clone()
+    }
+
+    fn player_atlas(app: &mut App) -> Handle<TextureAtlas> {
+        let mut players = app
+            .world
+            .query_filtered::<&Handle<TextureAtlas>, With<Player>>();
+        players.single(&app.world).clone()
+    }
+
+    #[test]
+    fn ghosts_use_the_animated_player_atlas() {
+        let mut app = App::new();
+        app.add_plugins(MinimalPlugins)
+            .add_plugins(AssetPlugin::default())
+            .init_asset::<Image>()
+            .init_asset::<TextureAtlas>()
+            .add_state::<GameState>()
+            .init_resource::<LevelWrap>()
+            .add_plugins(AnimationPlugin)
+            .add_systems(Update, (spawn_wrap_ghosts, update_wrap_ghosts).chain());
+
+        let ldtk_atlas = app
+            .world
+            .resource_mut::<Assets<TextureAtlas>>()
+            .add(TextureAtlas::new_empty(Handle::default(), Vec2::splat(16.)));
+        app.world.spawn((
+            Player,
+            Wraps,
+            SpriteSheetBundle {
+                texture_atlas: ldtk_atlas.clone(),
+                ..Default::default()
+            },
+        ));
+
+        app.update();
+        let animated_atlas = player_atlas(&mut app);
+        assert_ne!(animated_atlas, ldtk_atlas);
+        assert_eq!(ghost_atlas(&mut app), animated_atlas);
+
+        // and a ghost follows the player onto a new atlas later on
+        let mut players = app
+            .world
+            .query_filtered::<&mut Handle<TextureAtlas>, With<Player>>();
+        *players.single_mut(&mut app.world) = ldtk_atlas.clone();
+        app.update();
+        assert_eq!(ghost_atlas(&mut app), ldtk_atlas);
+    }
+}